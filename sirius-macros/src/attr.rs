@@ -0,0 +1,388 @@
+use crate::tag::TagType;
+
+use syn::{
+    Attribute, Expr, ExprPath, LitInt, LitStr, Token, Type, WherePredicate, punctuated::Punctuated,
+};
+
+/// Attributes placed on the type deriving `Sirius`, e.g. `#[sirius(bound = "T: Trait")]`.
+#[derive(Default)]
+pub struct ContainerAttrs {
+    /// Replaces the inferred `T: Sirius` bounds when present.
+    pub bound: Option<Punctuated<WherePredicate, Token![,]>>,
+
+    /// Derive the tag of every enum variant without an explicit tag from a hash of its name.
+    pub hash_tags: bool,
+
+    /// How enum variant tags are written, picked automatically when absent.
+    pub tag_type: Option<TagType>,
+
+    /// Current version of the struct, written as a header before its fields.
+    pub version: Option<u32>,
+
+    /// The struct is written exactly like its only field, holds the attribute's path.
+    pub transparent: Option<syn::Path>,
+
+    /// Type the value is converted to with `Into` and written as.
+    pub into: Option<Type>,
+
+    /// Type read and converted to the value with `From`.
+    pub from: Option<Type>,
+
+    /// Type read and converted to the value with `TryFrom`.
+    pub try_from: Option<Type>,
+
+    /// Function checking the value once it is read.
+    pub validate: Option<ExprPath>,
+
+    /// Implement `SiriusEncode` and `SiriusDe` instead of `Sirius`, so that the value can borrow
+    /// from the data it is read from, holds the attribute's path.
+    pub borrow: Option<syn::Path>,
+
+    /// Paths of the attributes above that are only valid on enums, for error reporting.
+    enum_only: Vec<syn::Path>,
+
+    /// Paths of the attributes above that are only valid on structs, for error reporting.
+    struct_only: Vec<syn::Path>,
+}
+
+impl ContainerAttrs {
+    pub fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut container = Self::default();
+        let mut from_attr = None;
+        let mut try_from_attr = None;
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("sirius")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("bound") {
+                    let bound: LitStr = meta.value()?.parse()?;
+                    container.bound = Some(bound.parse_with(Punctuated::parse_terminated)?);
+                    Ok(())
+                } else if meta.path.is_ident("hash_tags") {
+                    container.hash_tags = true;
+                    container.enum_only.push(meta.path);
+                    Ok(())
+                } else if meta.path.is_ident("tag_type") {
+                    container.tag_type = Some(TagType::from_name(&meta.value()?.parse()?)?);
+                    container.enum_only.push(meta.path);
+                    Ok(())
+                } else if meta.path.is_ident("version") {
+                    container.version = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+                    container.struct_only.push(meta.path);
+                    Ok(())
+                } else if meta.path.is_ident("transparent") {
+                    container.transparent = Some(meta.path.clone());
+                    container.struct_only.push(meta.path);
+                    Ok(())
+                } else if meta.path.is_ident("borrow") {
+                    container.borrow = Some(meta.path);
+                    Ok(())
+                } else if meta.path.is_ident("validate") {
+                    container.validate = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("into") {
+                    container.into = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("from") {
+                    container.from = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                    from_attr = Some(meta.path);
+                    Ok(())
+                } else if meta.path.is_ident("try_from") {
+                    container.try_from = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                    try_from_attr = Some(meta.path);
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown sirius container attribute"))
+                }
+            })?;
+        }
+
+        if let (Some(_), Some(path)) = (from_attr, try_from_attr) {
+            return Err(syn::Error::new_spanned(
+                path,
+                "`from` and `try_from` cannot be used together",
+            ));
+        }
+
+        if let Some(path) = &container.transparent
+            && (container.into.is_some() || container.read_proxy().is_some())
+        {
+            return Err(syn::Error::new_spanned(
+                path,
+                "transparent structs cannot have a proxy type",
+            ));
+        }
+
+        Ok(container)
+    }
+
+    /// Type read by `from` or `try_from`.
+    pub fn read_proxy(&self) -> Option<&Type> {
+        self.from.as_ref().or(self.try_from.as_ref())
+    }
+
+    /// Errors on attributes that only make sense on enums.
+    pub fn reject_enum_only(&self) -> syn::Result<()> {
+        match self.enum_only.first() {
+            Some(path) => Err(syn::Error::new_spanned(
+                path,
+                "this sirius attribute only applies to enums",
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Errors on attributes that only make sense on structs.
+    pub fn reject_struct_only(&self) -> syn::Result<()> {
+        match self.struct_only.first() {
+            Some(path) => Err(syn::Error::new_spanned(
+                path,
+                "this sirius attribute only applies to structs",
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Attributes placed on an enum variant, e.g. `#[sirius(tag = 7)]`.
+#[derive(Default)]
+pub struct VariantAttrs {
+    /// Explicit tag written before the variant's fields.
+    pub tag: Option<LitInt>,
+}
+
+impl VariantAttrs {
+    pub fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut variant = Self::default();
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("sirius")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("tag") {
+                    variant.tag = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown sirius variant attribute"))
+                }
+            })?;
+        }
+
+        Ok(variant)
+    }
+}
+
+/// Attributes placed on a field of a struct or enum variant, e.g. `#[sirius(skip)]`.
+#[derive(Default)]
+pub struct FieldAttrs {
+    /// The field is not written, and is filled in from `default` when reading.
+    pub skip: bool,
+
+    /// How to produce the field's value when it is not read from the data.
+    pub default: DefaultValue,
+
+    /// Function used instead of `Sirius::serialize`, set by `serialize_with` or `with`.
+    pub serialize_with: Option<ExprPath>,
+
+    /// Function used instead of `Sirius::deserialize`, set by `deserialize_with` or `with`.
+    pub deserialize_with: Option<ExprPath>,
+
+    /// First struct version the field is part of.
+    pub since: Option<u32>,
+
+    /// First struct version the field is no longer part of.
+    pub until: Option<u32>,
+
+    /// `since` or `until` attribute, for error reporting.
+    pub versioned: Option<syn::Path>,
+
+    /// Function checking the field's value right after it is read.
+    pub validate: Option<ExprPath>,
+
+    /// The field is written with `IntEncoding::Varint`, whatever the configuration.
+    pub varint: bool,
+
+    /// Variant of `sirius::LengthPrefix` the field is written with, whatever the configuration.
+    pub len: Option<syn::Ident>,
+}
+
+#[derive(Default)]
+pub enum DefaultValue {
+    /// `Default::default()`
+    #[default]
+    Trait,
+
+    /// `#[sirius(default = "path::to::function")]`, the function is called with no arguments.
+    Function(ExprPath),
+
+    /// `#[sirius(default = expression)]`, the expression is evaluated as-is.
+    Expr(Expr),
+}
+
+impl FieldAttrs {
+    pub fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut field = Self::default();
+        let mut default_attr = None;
+        let mut codec_attr = None;
+        let mut validate_attr = None;
+        // `varint` or `len`, which override the configuration
+        let mut config_attr = None;
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("sirius")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("skip") {
+                    field.skip = true;
+                    Ok(())
+                } else if meta.path.is_ident("default") {
+                    field.default = match meta.value()?.parse()? {
+                        Expr::Lit(syn::ExprLit {
+                            lit: syn::Lit::Str(path),
+                            ..
+                        }) => DefaultValue::Function(path.parse()?),
+                        expr => DefaultValue::Expr(expr),
+                    };
+                    default_attr = Some(meta.path);
+                    Ok(())
+                } else if meta.path.is_ident("with") {
+                    let module: ExprPath = meta.value()?.parse::<LitStr>()?.parse()?;
+                    field.serialize_with = Some(syn::parse_quote! { #module::serialize });
+                    field.deserialize_with = Some(syn::parse_quote! { #module::deserialize });
+                    codec_attr = Some(meta.path);
+                    Ok(())
+                } else if meta.path.is_ident("serialize_with") {
+                    field.serialize_with = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                    codec_attr = Some(meta.path);
+                    Ok(())
+                } else if meta.path.is_ident("deserialize_with") {
+                    field.deserialize_with = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                    codec_attr = Some(meta.path);
+                    Ok(())
+                } else if meta.path.is_ident("validate") {
+                    field.validate = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                    validate_attr = Some(meta.path);
+                    Ok(())
+                } else if meta.path.is_ident("varint") {
+                    field.varint = true;
+                    config_attr = Some(meta.path);
+                    Ok(())
+                } else if meta.path.is_ident("len") {
+                    field.len = Some(length_prefix(&meta.value()?.parse()?)?);
+                    config_attr = Some(meta.path);
+                    Ok(())
+                } else if meta.path.is_ident("since") {
+                    field.since = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+                    field.versioned = Some(meta.path);
+                    Ok(())
+                } else if meta.path.is_ident("until") {
+                    field.until = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+                    field.versioned = Some(meta.path);
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown sirius field attribute"))
+                }
+            })?;
+        }
+
+        if let Some(path) = default_attr
+            && !field.skip
+            && field.versioned.is_none()
+        {
+            return Err(syn::Error::new_spanned(
+                path,
+                "`default` only applies to fields marked with `skip`, `since` or `until`",
+            ));
+        }
+
+        if let (Some(since), Some(until)) = (field.since, field.until)
+            && since >= until
+        {
+            return Err(syn::Error::new_spanned(
+                &field.versioned,
+                "`since` must be smaller than `until`",
+            ));
+        }
+
+        if let Some(path) = &field.versioned
+            && field.skip
+        {
+            return Err(syn::Error::new_spanned(
+                path,
+                "skipped fields are never (de)serialized, remove the version range",
+            ));
+        }
+
+        if let Some(path) = &codec_attr
+            && field.skip
+        {
+            return Err(syn::Error::new_spanned(
+                path,
+                "skipped fields are never (de)serialized, remove the custom codec",
+            ));
+        }
+
+        if let Some(path) = validate_attr
+            && field.skip
+        {
+            return Err(syn::Error::new_spanned(
+                path,
+                "skipped fields are never deserialized, remove the validation",
+            ));
+        }
+
+        if let Some(path) = &config_attr {
+            let name = path
+                .get_ident()
+                .map(ToString::to_string)
+                .unwrap_or_default();
+
+            if field.skip {
+                return Err(syn::Error::new_spanned(
+                    path,
+                    format!("skipped fields are never (de)serialized, remove `{name}`"),
+                ));
+            }
+
+            if codec_attr.is_some() {
+                return Err(syn::Error::new_spanned(
+                    path,
+                    format!(
+                        "custom codecs are not given the encoding configuration, remove `{name}`"
+                    ),
+                ));
+            }
+        }
+
+        Ok(field)
+    }
+
+    /// Whether the field is written in payloads of struct version `version`.
+    pub fn present_in(&self, version: Option<u32>) -> bool {
+        let Some(version) = version else {
+            return true;
+        };
+
+        self.since.is_none_or(|since| since <= version)
+            && self.until.is_none_or(|until| version < until)
+    }
+
+    /// Whether the field's type has to implement `Sirius` for the generated impl to compile.
+    pub fn needs_sirius(&self) -> bool {
+        !self.skip && (self.serialize_with.is_none() || self.deserialize_with.is_none())
+    }
+}
+
+/// Variant of `sirius::LengthPrefix` named by a `len` attribute.
+fn length_prefix(name: &LitStr) -> syn::Result<syn::Ident> {
+    let variant = match name.value().as_str() {
+        "u8" => "U8",
+        "u16" => "U16",
+        "u32" => "U32",
+        "u64" => "U64",
+        "varint" => "Varint",
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "expected one of \"u8\", \"u16\", \"u32\", \"u64\" or \"varint\"",
+            ));
+        }
+    };
+
+    Ok(syn::Ident::new(variant, name.span()))
+}
@@ -1,258 +1,1208 @@
-use proc_macro::TokenStream;
-use quote::quote;
-use syn::Attribute;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
 
-pub fn derive(ast: &syn::DeriveInput) -> TokenStream {
+use crate::{
+    attr::{ContainerAttrs, DefaultValue, FieldAttrs},
+    tag::{self, TagType},
+};
+
+pub fn derive(ast: &syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
     let name = &ast.ident;
+    let attrs = ContainerAttrs::parse(&ast.attrs)?;
+
+    if let Some(path) = &attrs.borrow
+        && ast.generics.lifetimes().next().is_none()
+    {
+        return Err(syn::Error::new_spanned(
+            path,
+            "`borrow` needs a lifetime parameter for the data the value borrows",
+        ));
+    }
+    let borrowed = attrs.borrow.is_some();
+
+    let (generics, mut methods) = match &ast.data {
+        syn::Data::Struct(struct_data) => {
+            attrs.reject_enum_only()?;
+
+            let fields = Fields::parse(&struct_data.fields, attrs.version, name, None)?;
+            let generics = with_bounds(&ast.generics, &attrs, fields.iter(), borrowed);
+
+            let methods = match &attrs.transparent {
+                Some(path) => impl_transparent(&fields, path, borrowed)?,
+                None => impl_struct(name, &fields, borrowed),
+            };
+
+            (generics, methods)
+        }
+        syn::Data::Enum(enum_data) => {
+            attrs.reject_struct_only()?;
+
+            let variants = Variants::parse(name, &ast.attrs, &attrs, enum_data)?;
+            let fields = variants
+                .variants
+                .iter()
+                .flat_map(|variant| variant.fields.iter());
+            let generics = with_bounds(&ast.generics, &attrs, fields, borrowed);
+
+            (generics, impl_enum(name, &variants, borrowed))
+        }
+        syn::Data::Union(union_data) => {
+            return Err(syn::Error::new_spanned(
+                union_data.union_token,
+                "Sirius does not support unions, only structs and enums are supported",
+            ));
+        }
+    };
+
+    apply_proxies(name, &attrs, &mut methods, borrowed);
+    Ok(methods.into_impls(name, &generics, &attrs, borrowed))
+}
+
+/// The fields of a struct or of a single enum variant.
+struct Fields<'a> {
+    style: FieldsStyle,
+    fields: Vec<Field<'a>>,
+
+    /// Current version of a struct with `#[sirius(version = N)]`.
+    version: Option<u32>,
+
+    /// Name of the struct or enum the fields belong to, for error paths.
+    container: String,
+
+    /// Name of the enum variant the fields belong to, for error paths.
+    variant: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FieldsStyle {
+    Named,
+    Unnamed,
+    Unit,
+}
+
+struct Field<'a> {
+    field: &'a syn::Field,
+    attrs: FieldAttrs,
+
+    /// Name of the local variable holding this field while (de)serializing.
+    binding: proc_macro2::Ident,
+}
+
+impl<'a> Fields<'a> {
+    fn parse(
+        fields: &'a syn::Fields,
+        version: Option<u32>,
+        container: &syn::Ident,
+        variant: Option<&syn::Ident>,
+    ) -> syn::Result<Self> {
+        let style = match fields {
+            syn::Fields::Named(..) => FieldsStyle::Named,
+            syn::Fields::Unnamed(..) => FieldsStyle::Unnamed,
+            syn::Fields::Unit => FieldsStyle::Unit,
+        };
+
+        let fields = fields
+            .iter()
+            .enumerate()
+            .map(|(idx, field)| {
+                let attrs = FieldAttrs::parse(&field.attrs)?;
+
+                if let Some(path) = &attrs.versioned {
+                    let Some(version) = version else {
+                        return Err(syn::Error::new_spanned(
+                            path,
+                            "version ranges require `#[sirius(version = N)]` on the struct",
+                        ));
+                    };
+
+                    if attrs.since.max(attrs.until).is_some_and(|v| v > version) {
+                        return Err(syn::Error::new_spanned(
+                            path,
+                            format!("version range goes past the struct's version {version}"),
+                        ));
+                    }
+                }
+
+                Ok(Field {
+                    field,
+                    attrs,
+                    binding: make_ident(&format!("f{idx}")),
+                })
+            })
+            .collect::<syn::Result<_>>()?;
+
+        Ok(Self {
+            style,
+            fields,
+            version,
+            container: container.to_string(),
+            variant: variant.map(ToString::to_string),
+        })
+    }
+
+    /// Fields written by `serialize`, which are the ones in the current version.
+    fn written(&self) -> impl Iterator<Item = &Field<'a>> {
+        self.iter()
+            .filter(|field| !field.attrs.skip && field.attrs.present_in(self.version))
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Field<'a>> {
+        self.fields.iter()
+    }
+
+    /// Pattern binding every serialized field to its local variable, e.g. `{ a: f0, b: _ }`.
+    fn destructure(&self) -> proc_macro2::TokenStream {
+        let bindings = self.iter().map(|field| {
+            let binding = &field.binding;
+            let binding = if field.attrs.skip || !field.attrs.present_in(self.version) {
+                quote! { _ }
+            } else {
+                quote! { #binding }
+            };
+
+            match &field.field.ident {
+                Some(ident) => quote! { #ident: #binding },
+                None => binding,
+            }
+        });
+
+        self.wrap(bindings)
+    }
+
+    /// Expression building the value back from the local variables, e.g. `{ a: f0, b: f1 }`.
+    fn construct(&self) -> proc_macro2::TokenStream {
+        let bindings = self.iter().map(|field| {
+            let binding = &field.binding;
+            match &field.field.ident {
+                Some(ident) => quote! { #ident: #binding },
+                None => quote! { #binding },
+            }
+        });
+
+        self.wrap(bindings)
+    }
+
+    fn wrap(
+        &self,
+        items: impl Iterator<Item = proc_macro2::TokenStream>,
+    ) -> proc_macro2::TokenStream {
+        match self.style {
+            FieldsStyle::Named => quote! { { #(#items),* } },
+            FieldsStyle::Unnamed => quote! { ( #(#items),* ) },
+            FieldsStyle::Unit => proc_macro2::TokenStream::new(),
+        }
+    }
+
+    /// Statements writing every serialized field, expects the fields to be bound by
+    /// [`Fields::destructure`]. `borrowed` writes them with `SiriusEncode` instead of `Sirius`.
+    fn serialize(&self, borrowed: bool) -> proc_macro2::TokenStream {
+        let serialize_with = match borrowed {
+            true => quote! { sirius::SiriusEncode::encode_with },
+            false => quote! { sirius::Sirius::serialize_with },
+        };
+
+        let fields = self.written().map(|field| {
+            let binding = &field.binding;
+            let config = field.config();
+            match &field.attrs.serialize_with {
+                Some(path) => quote! {
+                    bytes_written += #path(#binding, output)?;
+                },
+                None => quote_spanned! { field.field.ty.span()=>
+                    bytes_written += #serialize_with(#binding, output, #config)?;
+                },
+            }
+        });
+
+        quote! { #(#fields)* }
+    }
+
+    /// Statements adding the length of every serialized field to the local `len`, expects the
+    /// fields to be bound by [`Fields::destructure`].
+    fn encoded_len(&self, borrowed: bool) -> proc_macro2::TokenStream {
+        let encoded_len_with = match borrowed {
+            true => quote! { sirius::SiriusEncode::encode_len_with },
+            false => quote! { sirius::Sirius::encoded_len_with },
+        };
+
+        let fields = self.written().map(|field| {
+            let binding = &field.binding;
+            let config = field.config();
+            match &field.attrs.serialize_with {
+                Some(path) => quote! {
+                    len += {
+                        let mut counter = sirius::ByteCounter::default();
+                        _ = #path(#binding, &mut counter);
+                        counter.0
+                    };
+                },
+                None => quote_spanned! { field.field.ty.span()=>
+                    len += #encoded_len_with(#binding, #config);
+                },
+            }
+        });
+
+        quote! { #(#fields)* }
+    }
+
+    /// Statements reading every field from `source` into its local variable, advancing `offset`.
+    ///
+    /// Fields with a version range read the payload's version from the local `version`.
+    fn deserialize(&self, source: Source) -> proc_macro2::TokenStream {
+        let fields = self.iter().enumerate().map(|(idx, field)| {
+            let binding = &field.binding;
+            let ty = &field.field.ty;
+
+            let default = match &field.attrs.default {
+                DefaultValue::Trait => quote! { ::core::default::Default::default() },
+                DefaultValue::Function(path) => quote! { #path() },
+                DefaultValue::Expr(expr) => quote! { #expr },
+            };
+
+            if field.attrs.skip {
+                return quote! { let #binding: #ty = #default; };
+            }
+
+            let field_name = match &field.field.ident {
+                Some(ident) => ident.to_string(),
+                None => idx.to_string(),
+            };
+
+            let container = &self.container;
+            let variant = self.variant.iter();
+            let context = quote! {
+                .within(offset, sirius::PathSegment::Field(#field_name))
+                #(.within(0, sirius::PathSegment::Variant(#variant)))*
+                .in_type(#container)
+            };
+
+            let validate = field
+                .attrs
+                .validate
+                .as_ref()
+                .map(|validate| validate_value(validate, binding, &context));
 
-    match &ast.data {
-        syn::Data::Struct(struct_data) => impl_struct(name, struct_data),
-        syn::Data::Enum(enum_data) => impl_enum(name, enum_data, ast.attrs.clone()),
-        syn::Data::Union(_) => {
-            panic!("Sirius does not support unions, only structs and enums are supported")
+            let deserialize = match &field.attrs.deserialize_with {
+                Some(path) => source.codec(path),
+                None => source.sirius(ty, &field.config()),
+            };
+
+            let mut read = quote! {
+                #deserialize.map_err(|error| error #context)?
+            };
+
+            if field.attrs.versioned.is_some() {
+                let since = field.attrs.since.map(|since| {
+                    let since = proc_macro2::Literal::u64_unsuffixed(since as u64);
+                    quote! { version >= #since }
+                });
+                let until = field.attrs.until.map(|until| {
+                    let until = proc_macro2::Literal::u64_unsuffixed(until as u64);
+                    quote! { version < #until }
+                });
+                let present = since.into_iter().chain(until);
+
+                read = quote! {
+                    if #(#present)&&* { #read } else { (#default, 0) }
+                };
+            }
+
+            quote! {
+                let (#binding, inc): (#ty, usize) = #read;
+                #validate
+                offset += inc;
+            }
+        });
+
+        quote! { #(#fields)* }
+    }
+}
+
+impl Fields<'_> {
+    /// Expression describing every field that is written in any version, as a
+    /// `Vec<sirius::schema::FieldSchema>`.
+    fn schema(&self) -> proc_macro2::TokenStream {
+        let fields = self
+            .iter()
+            .enumerate()
+            .filter(|(_, field)| !field.attrs.skip)
+            .map(|(idx, field)| {
+                let name = match &field.field.ident {
+                    Some(ident) => ident.to_string(),
+                    None => idx.to_string(),
+                };
+                let since = quote_option(field.attrs.since);
+                let until = quote_option(field.attrs.until);
+                let varint = field.attrs.varint;
+                let length_prefix = quote_option(
+                    field
+                        .attrs
+                        .len
+                        .as_ref()
+                        .map(|len| quote! { sirius::LengthPrefix::#len }),
+                );
+                let schema = field.schema();
+
+                quote! {
+                    sirius::schema::FieldSchema {
+                        name: #name,
+                        since: #since,
+                        until: #until,
+                        varint: #varint,
+                        length_prefix: #length_prefix,
+                        schema: #schema,
+                    }
+                }
+            });
+
+        quote! { sirius::__private::vec![#(#fields),*] }
+    }
+}
+
+impl Field<'_> {
+    /// Expression of the `&sirius::Config` the field is written with, the `config` of the
+    /// whole value with the field's overrides applied.
+    fn config(&self) -> proc_macro2::TokenStream {
+        let varint = self
+            .attrs
+            .varint
+            .then(|| quote! { .int_encoding(sirius::IntEncoding::Varint) });
+        let len = self
+            .attrs
+            .len
+            .as_ref()
+            .map(|len| quote! { .length_prefix(sirius::LengthPrefix::#len) });
+
+        match (&varint, &len) {
+            (None, None) => quote! { config },
+            _ => quote! { &config #varint #len },
+        }
+    }
+
+    /// Expression describing the field's value as a `sirius::schema::Schema`.
+    fn schema(&self) -> proc_macro2::TokenStream {
+        let ty = &self.field.ty;
+        let codec = self
+            .attrs
+            .serialize_with
+            .as_ref()
+            .or(self.attrs.deserialize_with.as_ref());
+
+        match codec {
+            Some(path) => {
+                let path = quote! { #path }.to_string().replace(' ', "");
+                quote! { sirius::schema::Schema::Opaque(#path) }
+            }
+            None => quote_spanned! { ty.span()=>
+                <#ty as sirius::SiriusSchema>::describe(context)
+            },
         }
     }
 }
 
+/// The variants of an enum together with the tags identifying them.
+struct Variants<'a> {
+    tag_type: TagType,
+
+    /// The tag type was picked from the largest tag rather than written out.
+    auto_tag_type: bool,
+
+    variants: Vec<Variant<'a>>,
+}
+
+struct Variant<'a> {
+    ident: &'a syn::Ident,
+    fields: Fields<'a>,
+    tag: u64,
+}
+
+impl<'a> Variants<'a> {
+    fn parse(
+        name: &syn::Ident,
+        attrs: &[syn::Attribute],
+        container: &ContainerAttrs,
+        syn::DataEnum { variants, .. }: &'a syn::DataEnum,
+    ) -> syn::Result<Self> {
+        let (tag_type, auto_tag_type, tags) =
+            tag::resolve(attrs, &variants.iter().collect::<Vec<_>>(), container)?;
+
+        let variants = variants
+            .iter()
+            .zip(tags)
+            .map(|(variant, tag)| {
+                Ok(Variant {
+                    ident: &variant.ident,
+                    fields: Fields::parse(&variant.fields, None, name, Some(&variant.ident))?,
+                    tag,
+                })
+            })
+            .collect::<syn::Result<_>>()?;
+
+        Ok(Self {
+            tag_type,
+            auto_tag_type,
+            variants,
+        })
+    }
+}
+
 /// # Struct Serialization & Deserialization
 ///
 /// ## Serialization
-/// The generated `serialize` method writes each field to the provided output sequentially:
+/// The generated `serialize_with` method writes each field to the provided output sequentially,
+/// `serialize` calls it with the default `Config`:
 /// ```no_run,rust
-/// fn serialize(&self, output: &mut impl std::io::Write) -> usize {
+/// fn serialize_with(
+///     &self,
+///     output: &mut impl sirius::Writer,
+///     config: &sirius::Config,
+/// ) -> Result<usize, sirius::SiriusError> {
+///     let Self { field1: f0, field2: f1, /* ... */ } = self;
 ///     let mut bytes_written = 0;
-///     bytes_written += sirius::Sirius::serialize(&self.field1, output);
-///     bytes_written += sirius::Sirius::serialize(&self.field2, output);
+///     bytes_written += sirius::Sirius::serialize_with(f0, output, config)?;
+///     bytes_written += sirius::Sirius::serialize_with(f1, output, config)?;
 ///     // ...
-///     bytes_written
+///     Ok(bytes_written)
 /// }
 /// ```
 ///
+/// The generated `encoded_len_with` adds up `sirius::Sirius::encoded_len_with(f0, config)` and
+/// so on for the same fields, the lengths of fields with a custom codec are counted by running
+/// it on a `sirius::ByteCounter`.
+///
 /// ## Deserialization
-/// The generated `deserialize` method reads each field in the same order:
+/// The generated `deserialize_with` method reads each field in the same order:
 /// ```no_run,rust
-/// fn deserialize(data: &[u8]) -> Result<(Self, usize), sirius::SiriusError> {
+/// fn deserialize_with(
+///     data: &[u8],
+///     config: &sirius::Config,
+/// ) -> Result<(Self, usize), sirius::SiriusError> {
+///     let allocated = Cell::new(0);
+///     let config = &config.nested(&allocated)?; // checks the `DecodeLimits`
 ///     let mut offset = 0;
-///     let field1 = T1::deserialize(data.get(offset..).ok_or(...)?)?; offset += field1.1;
-///     let field2 = T2::deserialize(data.get(offset..).ok_or(...)?)?; offset += field2.1;
+///     let (f0, inc) = T1::deserialize_with(data.get(offset..).ok_or(...)?, config)?; offset += inc;
+///     let (f1, inc) = T2::deserialize_with(data.get(offset..).ok_or(...)?, config)?; offset += inc;
 ///     // ...
-///     Ok((Self { field1: field1.0, field2: field2.0, /* ... */ }, offset))
+///     Ok((Self { field1: f0, field2: f1, /* ... */ }, offset))
 /// }
 /// ```
 ///
-/// Tuple structs are supported as well; construction switches to `Self(field1.0, field2.0, ...)`.
-fn impl_struct(name: &syn::Ident, syn::DataStruct { fields, .. }: &syn::DataStruct) -> TokenStream {
-    let is_tuple_struct = matches!(fields, syn::Fields::Unnamed(..));
-    let (serialize_fields, deserialize_fields, collection) = (
-        // serialization
-        fields.iter().enumerate().map(|(field_idx, field)| {
-            let field_name = field
-                .ident
-                .as_ref()
-                .map(|i| quote! { #i })
-                .unwrap_or_else(|| {
-                    let literal = proc_macro2::Literal::usize_unsuffixed(field_idx);
-                    quote! { #literal }
-                });
+/// Tuple structs are supported as well; construction switches to `Self(f0, f1, ...)`.
+///
+/// Fields marked with `#[sirius(skip)]` are left out of `serialize`, `deserialize` fills them
+/// in from `Default::default()` or from the `#[sirius(default = ...)]` function/expression.
+///
+/// `#[sirius(with = "module")]` replaces the calls with `module::serialize(f0, output)` and
+/// `module::deserialize(data)`, which do not get the `Config`. `serialize_with`/`deserialize_with`
+/// replace only one side.
+///
+/// `#[sirius(varint)]` passes `&config.int_encoding(sirius::IntEncoding::Varint)` instead of
+/// `config` for the field, which writes the integers, length prefixes and tags inside it as
+/// varints. `#[sirius(len = "u16")]` likewise passes `&config.length_prefix(...)`.
+///
+/// ## Borrowing
+/// With `#[sirius(borrow)]` the same bodies implement `SiriusEncode::encode_with`, writing the
+/// fields with `SiriusEncode`, and `SiriusDe<'__de>::deserialize_borrowed_with`, reading them
+/// with `SiriusDe<'__de>` from `data: &'__de [u8]`, where `'__de` outlives every lifetime
+/// parameter of the type. There is no `deserialize_from`, a reader has nothing to borrow from.
+///
+/// ## Versioning
+/// With `#[sirius(version = N)]` the struct starts with `N` as a varint, followed by the fields
+/// that are part of version `N`. A field with `#[sirius(since = S, until = U)]` is part of the
+/// versions `S..U`; when reading a payload of a version it is not part of, the field is filled
+/// in like a skipped field. Payloads newer than `N` are rejected.
+fn impl_struct(name: &syn::Ident, fields: &Fields, borrowed: bool) -> Methods {
+    let version = fields
+        .version
+        .map(|version| proc_macro2::Literal::u64_unsuffixed(version as u64));
 
-            quote! {
-                bytes_written += sirius::Sirius::serialize(&self.#field_name, output)?;
-            }
-        }),
-        // deserialization
-        fields.iter().enumerate().map(|(idx, field)| {
-            let ty = &field.ty;
-            let field_var_ident = make_ident(&format!("f{idx}"));
+    let serialize_version = version.as_ref().map(|version| {
+        quote! { bytes_written += sirius::varint::serialize(#version, output)?; }
+    });
+    let version_len = version
+        .as_ref()
+        .map(|version| quote! { len += sirius::varint::encoded_len(#version); });
 
-            quote! {
-                let #field_var_ident = <#ty as sirius::Sirius>::deserialize(data.get(offset..)
-                    .ok_or(sirius::SiriusError::NotEnoughData)?)?;
-                offset += #field_var_ident.1;
-            }
-        }),
-        // collection
-        fields.iter().enumerate().map(|(idx, field)| {
-            let field_var_ident = make_ident(&format!("f{idx}"));
+    let deserialize_version = |source: Source| {
+        let version = version.as_ref()?;
+        let deserialize = source.varint();
 
-            if is_tuple_struct {
-                quote! { #field_var_ident.0 }
-            } else {
-                let field_name = field.ident.as_ref().unwrap();
-                quote! { #field_name: #field_var_ident.0 }
-            }
-        }),
-    );
+        Some(quote! {
+            let (version, inc) = #deserialize.map_err(|error| error.in_type(stringify!(#name)))?;
+            offset += inc;
 
-    let collection = if is_tuple_struct {
-        quote! { Self(#(#collection),*) }
-    } else {
-        quote! { Self{ #(#collection),* } }
+            if version > #version {
+                return Err(sirius::SiriusError::parsing_error(
+                    stringify!(#name),
+                    sirius::__message!(
+                        "unsupported version {}, the newest known version is {}",
+                        version, #version
+                    ),
+                )
+                .in_type(stringify!(#name)));
+            }
+        })
     };
 
-    quote! {
-        impl sirius::Sirius for #name {
-            fn serialize(&self, output: &mut impl std::io::Write) -> Result<usize, sirius::SiriusError> {
-                let mut bytes_written = 0;
-                #(#serialize_fields)*
-                Ok(bytes_written)
-            }
+    let destructure = fields.destructure();
+    let serialize = fields.serialize(borrowed);
+    let encoded_len = fields.encoded_len(borrowed);
+    let construct = fields.construct();
+    let deserialize = |source| {
+        let deserialize_version = deserialize_version(source);
+        let deserialize = fields.deserialize(source);
 
-            fn deserialize(data: &[u8]) -> Result<(Self, usize), sirius::SiriusError> {
-                let mut offset = 0;
-                #(#deserialize_fields)*
+        quote! {
+            let mut offset = 0;
+            #deserialize_version
+            #deserialize
 
-                Ok((
-                    #collection, offset
-                ))
-            }
+            let value = Self #construct;
         }
+    };
+    let (deserialize, deserialize_from) = Source::bodies(borrowed, deserialize);
+    let version_schema = quote_option(fields.version);
+    let fields_schema = fields.schema();
+
+    Methods {
+        serialize: quote! {
+            let Self #destructure = self;
+            let mut bytes_written = 0;
+            #serialize_version
+            #serialize
+            Ok(bytes_written)
+        },
+        encoded_len: quote! {
+            let Self #destructure = self;
+            let mut len = 0;
+            #version_len
+            #encoded_len
+            len
+        },
+        deserialize,
+        deserialize_from,
+        describe: quote! {
+            context.named(::core::any::type_name::<Self>(), stringify!(#name), |context| {
+                sirius::schema::Schema::Struct(sirius::schema::StructSchema {
+                    name: stringify!(#name),
+                    version: #version_schema,
+                    fields: #fields_schema,
+                })
+            })
+        },
     }
-    .into()
 }
 
-fn impl_enum(
-    name: &syn::Ident,
-    syn::DataEnum { variants, .. }: &syn::DataEnum,
-    _attrs: Vec<Attribute>,
-) -> TokenStream {
-    let num_variants = variants.len();
-
-    if num_variants > u8::MAX as usize {
-        panic!(
-            "Sirius does not support enums with more than 255 variants, found {}",
-            num_variants
-        );
+/// # Transparent Structs
+///
+/// A struct with `#[sirius(transparent)]` must have exactly one field that is not skipped, and
+/// is written exactly like that field. The generated `serialize`, `deserialize` and `describe`
+/// forward to the field's, skipped fields are filled in like for any other struct.
+fn impl_transparent(
+    fields: &Fields,
+    transparent: &syn::Path,
+    borrowed: bool,
+) -> syn::Result<Methods> {
+    if fields.version.is_some() {
+        return Err(syn::Error::new_spanned(
+            transparent,
+            "transparent structs cannot be versioned",
+        ));
     }
 
-    let serialize = variants.iter().enumerate().map(|(variant_idx, variant)| {
-        let (destructure, serialize) = match &variant.fields {
-            syn::Fields::Unnamed(unnamed_fields) => {
-                let field_idents = unnamed_fields
-                    .unnamed
-                    .iter()
-                    .enumerate()
-                    .map(|(field_idx, _)| {
-                        proc_macro2::Ident::new(
-                            &format!("v{variant_idx}f{field_idx}"),
-                            proc_macro2::Span::call_site(),
-                        )
-                    });
+    let mut written = fields.written();
+    let (Some(field), None) = (written.next(), written.next()) else {
+        return Err(syn::Error::new_spanned(
+            transparent,
+            "transparent structs must have exactly one field that is not skipped",
+        ));
+    };
 
-                let destructure = field_idents
-                    .clone()
-                    .map(|field_ident| quote! { #field_ident });
+    let destructure = fields.destructure();
+    let serialize = fields.serialize(borrowed);
+    let encoded_len = fields.encoded_len(borrowed);
+    let construct = fields.construct();
+    let deserialize = |source| {
+        let deserialize = fields.deserialize(source);
 
-                let serialize = field_idents
-                    .map(|field_ident| quote! { bytes_written += sirius::Sirius::serialize(#field_ident, output)?; });
+        quote! {
+            let mut offset = 0;
+            #deserialize
+
+            let value = Self #construct;
+        }
+    };
+
+    let (deserialize, deserialize_from) = Source::bodies(borrowed, deserialize);
+
+    Ok(Methods {
+        serialize: quote! {
+            let Self #destructure = self;
+            let mut bytes_written = 0;
+            #serialize
+            Ok(bytes_written)
+        },
+        encoded_len: quote! {
+            let Self #destructure = self;
+            let mut len = 0;
+            #encoded_len
+            len
+        },
+        deserialize,
+        deserialize_from,
+        describe: field.schema(),
+    })
+}
 
-                (quote! { (#(#destructure)*) }, quote! { #(#serialize)* })
+/// # Enum Serialization & Deserialization
+///
+/// Every variant is written as its tag followed by its fields, which are handled the same way
+/// as the fields of a struct. See [`tag::resolve`] for how tags and the type they are written
+/// as are picked.
+fn impl_enum(name: &syn::Ident, variants: &Variants, borrowed: bool) -> Methods {
+    let serialize = variants.variants.iter().map(|variant| {
+        let variant_name = variant.ident;
+        let tag = variants.tag_type.serialize(variant.tag);
+        let destructure = variant.fields.destructure();
+        let serialize = variant.fields.serialize(borrowed);
+
+        quote! {
+            Self::#variant_name #destructure => {
+                #tag
+                #serialize
             }
+        }
+    });
 
-            syn::Fields::Named(named_fields) => {
-                let field_idents = named_fields.named.iter().map(|i| i.ident.as_ref().unwrap());
+    let encoded_len = variants.variants.iter().map(|variant| {
+        let variant_name = variant.ident;
+        let tag = variants.tag_type.encoded_len(variant.tag);
+        let destructure = variant.fields.destructure();
+        let encoded_len = variant.fields.encoded_len(borrowed);
 
-                let destructure = {
-                    let field_idents = field_idents.clone();
-                    quote! { { #(#field_idents),* } }
-                };
+        quote! {
+            Self::#variant_name #destructure => {
+                #tag
+                #encoded_len
+            }
+        }
+    });
 
-                let serialize = field_idents
-                    .map(|field_ident| quote! { bytes_written += sirius::Sirius::serialize(#field_ident, output)?; });
+    let deserialize = |source| {
+        let deserialize_tag = variants.tag_type.deserialize(source);
+        let variants = variants.variants.iter().map(|variant| {
+            let variant_name = variant.ident;
+            let tag = variants.tag_type.literal(variant.tag);
+            let deserialize = variant.fields.deserialize(source);
+            let construct = variant.fields.construct();
 
-                (destructure, quote! { #(#serialize)* })
+            quote! {
+                #tag => {
+                    #deserialize
+                    Self::#variant_name #construct
+                }
             }
+        });
 
-            syn::Fields::Unit => (
-                proc_macro2::TokenStream::new(),
-                proc_macro2::TokenStream::new(),
-            ),
-        };
+        quote! {
+            let mut offset = 0;
+            let (variant_tag, shift) =
+                #deserialize_tag.map_err(|error| error.in_type(stringify!(#name)))?;
+
+            offset += shift;
 
-        let variant_name = &variant.ident;
+            let value = match variant_tag {
+                #(#variants)*
+
+                _ => return Err(sirius::SiriusError::parsing_error(
+                    stringify!(#name),
+                    sirius::__message!("invalid variant tag: {}", variant_tag),
+                )
+                .in_type(stringify!(#name))),
+            };
+        }
+    };
+
+    let (deserialize, deserialize_from) = Source::bodies(borrowed, deserialize);
+    let tag_type_schema = variants.tag_type.schema();
+    let auto_tag_type = variants.auto_tag_type;
+    let variants_schema = variants.variants.iter().map(|variant| {
+        let variant_name = variant.ident.to_string();
+        let tag = variant.tag;
+        let fields = variant.fields.schema();
 
         quote! {
-            Self::#variant_name #destructure => {
-                bytes_written += (#variant_idx as u8).serialize(output)?;
-                #serialize
+            sirius::schema::VariantSchema {
+                name: #variant_name,
+                tag: #tag,
+                fields: #fields,
             }
         }
     });
 
-    let deserialize = variants.iter().enumerate().map(|(variant_idx, variant)| {
-        let deserialize = match &variant.fields {
-            syn::Fields::Unnamed(unnamed_fields) => {
-                let deserializer = unnamed_fields.unnamed.iter().map(
-                    |syn::Field { ty, .. }| quote! {{
-                        let (data, inc) = <#ty as sirius::Sirius>::deserialize(&data.get(offset..).ok_or(sirius::SiriusError::NotEnoughData)?)?;
-                        offset += inc;
-                        data
-                    }}
-                );
+    Methods {
+        serialize: quote! {
+            let mut bytes_written = 0;
 
-                quote! { (#(#deserializer)*) }
+            match self {
+                #(#serialize)*
             }
 
-            syn::Fields::Named(named_fields) => {
-                let deserializer= named_fields.named.iter().map(
-                    |syn::Field { ident, ty, .. }| {
-                        let ident = ident.as_ref().unwrap();
-                        quote! {
-                            #ident: {
-                                let (data, inc) = <#ty as sirius::Sirius>::deserialize(&data.get(offset..).ok_or(sirius::SiriusError::NotEnoughData)?)?;
-                                offset += inc;
-                                data
-                            },
-                        }
-                    }
-                );
+            Ok(bytes_written)
+        },
+        encoded_len: quote! {
+            let mut len = 0;
+
+            match self {
+                #(#encoded_len)*
+            }
+
+            len
+        },
+        deserialize,
+        deserialize_from,
+        describe: quote! {
+            context.named(::core::any::type_name::<Self>(), stringify!(#name), |context| {
+                sirius::schema::Schema::Enum(sirius::schema::EnumSchema {
+                    name: stringify!(#name),
+                    tag_type: #tag_type_schema,
+                    auto_tag_type: #auto_tag_type,
+                    variants: sirius::__private::vec![#(#variants_schema),*],
+                })
+            })
+        },
+    }
+}
+
+/// # Proxy Types
+///
+/// `#[sirius(into = "Proxy")]` writes a clone of the value converted to `Proxy` with `Into`.
+/// `#[sirius(from = "Proxy")]` and `#[sirius(try_from = "Proxy")]` read a `Proxy` and convert
+/// it with `From` or `TryFrom`, a failed conversion is returned as a `ParsingError` carrying the
+/// conversion error's message. The schema is the proxy's, the `into` one when both sides have
+/// a proxy.
+fn apply_proxies(name: &syn::Ident, attrs: &ContainerAttrs, methods: &mut Methods, borrowed: bool) {
+    if let Some(proxy) = &attrs.from {
+        let deserialize = |source: Source| {
+            let deserialize = source.whole(proxy);
+
+            quote! {
+                let (proxy, offset) = #deserialize?;
+                let value = <Self as ::core::convert::From<#proxy>>::from(proxy);
+            }
+        };
+
+        (methods.deserialize, methods.deserialize_from) = Source::bodies(borrowed, deserialize);
+        methods.describe = quote! { <#proxy as sirius::SiriusSchema>::describe(context) };
+    }
+
+    if let Some(proxy) = &attrs.try_from {
+        let deserialize = |source: Source| {
+            let deserialize = source.whole(proxy);
 
-                quote! { { #(#deserializer)* } }
+            quote! {
+                let (proxy, offset) = #deserialize?;
+                let value = match <Self as ::core::convert::TryFrom<#proxy>>::try_from(proxy) {
+                    Ok(value) => value,
+                    Err(error) => return Err(
+                        sirius::SiriusError::parsing_error(
+                            stringify!(#name),
+                            sirius::__message!("{}", error),
+                        )
+                            .in_type(stringify!(#name))
+                    ),
+                };
             }
+        };
 
-            syn::Fields::Unit => proc_macro2::TokenStream::new(),
+        (methods.deserialize, methods.deserialize_from) = Source::bodies(borrowed, deserialize);
+        methods.describe = quote! { <#proxy as sirius::SiriusSchema>::describe(context) };
+    }
+
+    if let Some(proxy) = &attrs.into {
+        methods.serialize = quote! {
+            let proxy: #proxy = ::core::convert::Into::into(::core::clone::Clone::clone(self));
+            <#proxy as sirius::Sirius>::serialize_with(&proxy, output, config)
+        };
+        methods.encoded_len = quote! {
+            let proxy: #proxy = ::core::convert::Into::into(::core::clone::Clone::clone(self));
+            <#proxy as sirius::Sirius>::encoded_len_with(&proxy, config)
         };
+        methods.describe = quote! { <#proxy as sirius::SiriusSchema>::describe(context) };
+    }
+}
+
+/// What the generated `deserialize` code reads from.
+#[derive(Clone, Copy)]
+pub enum Source {
+    /// The local `data: &[u8]` of `Sirius::deserialize`, read starting at `offset`.
+    Slice,
+
+    /// The local `reader: &mut impl Read` of `Sirius::deserialize_from`.
+    Reader,
+
+    /// The local `data: &'__de [u8]` of `SiriusDe::deserialize_borrowed`, read starting at
+    /// `offset`.
+    Borrowed,
+}
 
-        let variant_idx = variant_idx as u8;
-        let variant_name = &variant.ident;
+impl Source {
+    /// The bodies of `Methods::deserialize` and `Methods::deserialize_from` built by `body`,
+    /// reading from `Source::Borrowed` if `borrowed`, which has no `deserialize_from`.
+    fn bodies(
+        borrowed: bool,
+        body: impl Fn(Self) -> proc_macro2::TokenStream,
+    ) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+        match borrowed {
+            true => (body(Self::Borrowed), proc_macro2::TokenStream::new()),
+            false => (body(Self::Slice), body(Self::Reader)),
+        }
+    }
+
+    /// Expression reading a `ty` encoded as the `&sirius::Config` expression `config` says,
+    /// evaluating to `Result<(ty, bytes_read), SiriusError>`.
+    pub fn sirius(
+        self,
+        ty: &syn::Type,
+        config: &proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        match self {
+            Self::Slice => {
+                let remaining = self.remaining();
+                quote_spanned! { ty.span()=>
+                    <#ty as sirius::Sirius>::deserialize_with(#remaining, #config)
+                }
+            }
+            Self::Reader => quote_spanned! { ty.span()=>
+                <#ty as sirius::Sirius>::deserialize_from_with(reader, #config)
+            },
+            Self::Borrowed => {
+                let remaining = self.remaining();
+                quote_spanned! { ty.span()=>
+                    <#ty as sirius::SiriusDe<'__de>>::deserialize_borrowed_with(#remaining, #config)
+                }
+            }
+        }
+    }
 
+    /// Same as [`Source::sirius`], for a `ty` that makes up the whole value, before `offset`
+    /// is declared.
+    fn whole(self, ty: &syn::Type) -> proc_macro2::TokenStream {
+        match self {
+            Self::Slice => quote! { <#ty as sirius::Sirius>::deserialize_with(data, config) },
+            Self::Reader => {
+                quote! { <#ty as sirius::Sirius>::deserialize_from_with(reader, config) }
+            }
+            Self::Borrowed => quote! {
+                <#ty as sirius::SiriusDe<'__de>>::deserialize_borrowed_with(data, config)
+            },
+        }
+    }
+
+    /// Expression reading a value with `path`, which has the signature of `Sirius::deserialize`.
+    fn codec(self, path: &syn::ExprPath) -> proc_macro2::TokenStream {
+        match self {
+            Self::Slice | Self::Borrowed => {
+                let remaining = self.remaining();
+                quote! { #path(#remaining) }
+            }
+            Self::Reader => quote! { sirius::read::read_with(reader, #path) },
+        }
+    }
+
+    /// Expression reading a varint, evaluating to `Result<(u64, bytes_read), SiriusError>`.
+    pub fn varint(self) -> proc_macro2::TokenStream {
+        match self {
+            Self::Slice | Self::Borrowed => {
+                let remaining = self.remaining();
+                quote! { sirius::varint::deserialize(#remaining) }
+            }
+            Self::Reader => quote! { sirius::varint::deserialize_from(reader) },
+        }
+    }
+
+    fn remaining(self) -> proc_macro2::TokenStream {
         quote! {
-            #variant_idx => Self::#variant_name #deserialize,
+            data.get(offset..)
+                .ok_or_else(|| sirius::SiriusError::not_enough_data(offset, data.len()))?
         }
-    });
+    }
+}
 
-    quote! {
-        impl sirius::Sirius for #name {
-            fn serialize(&self, output: &mut impl std::io::Write) -> Result<usize, sirius::SiriusError> {
-                let mut bytes_written = 0;
+/// Bodies of the methods of the generated `Sirius` and `SiriusSchema` impls.
+struct Methods {
+    serialize: proc_macro2::TokenStream,
+
+    /// Statements evaluating to the number of bytes `serialize` writes.
+    encoded_len: proc_macro2::TokenStream,
+
+    /// Statements reading `data` into the locals `value` and `offset`, the number of bytes read.
+    deserialize: proc_macro2::TokenStream,
+
+    /// Same as `deserialize`, reading from `reader` instead.
+    deserialize_from: proc_macro2::TokenStream,
+
+    describe: proc_macro2::TokenStream,
+}
+
+impl Methods {
+    fn into_impls(
+        self,
+        name: &syn::Ident,
+        generics: &syn::Generics,
+        attrs: &ContainerAttrs,
+        borrowed: bool,
+    ) -> proc_macro2::TokenStream {
+        let Self {
+            serialize,
+            encoded_len,
+            deserialize,
+            deserialize_from,
+            describe,
+        } = self;
+        let validate = attrs.validate.as_ref().map(|validate| {
+            let name = name.to_string();
+            validate_value(validate, &make_ident("value"), &quote! { .in_type(#name) })
+        });
+        // With `borrow`, `'__de` outlives every lifetime of the type, so that its fields can
+        // borrow for them. Every impl declares it, as the bounds of type parameters name it
+        let mut de_generics = generics.clone();
+        if borrowed {
+            let lifetimes = generics.lifetimes().map(|param| &param.lifetime);
+            de_generics
+                .params
+                .insert(0, syn::parse_quote! { '__de: #(#lifetimes)+* });
+        } else {
+            de_generics.params.insert(0, syn::parse_quote! { '__de });
+        }
+        let (de_impl_generics, _, _) = de_generics.split_for_impl();
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+        let impl_generics = match borrowed {
+            true => quote! { #de_impl_generics },
+            false => quote! { #impl_generics },
+        };
+
+        let schema = quote! {
+            impl #impl_generics sirius::SiriusSchema for #name #ty_generics #where_clause {
+                sirius::__alloc! {
+                    fn describe(context: &mut sirius::schema::SchemaContext) -> sirius::schema::Schema {
+                        #describe
+                    }
+                }
+            }
+        };
+
+        if borrowed {
+            return quote! {
+                impl #impl_generics sirius::SiriusEncode for #name #ty_generics #where_clause {
+                    #[allow(unused_variables)]
+                    fn encode_with(
+                        &self,
+                        output: &mut impl sirius::Writer,
+                        config: &sirius::Config,
+                    ) -> Result<usize, sirius::SiriusError> {
+                        #serialize
+                    }
+
+                    #[allow(unused_variables)]
+                    fn encode_len_with(&self, config: &sirius::Config) -> usize {
+                        #encoded_len
+                    }
+                }
+
+                impl #de_impl_generics sirius::SiriusDe<'__de> for #name #ty_generics #where_clause {
+                    #[allow(unused_variables)]
+                    fn deserialize_borrowed_with(
+                        data: &'__de [u8],
+                        config: &sirius::Config,
+                    ) -> Result<(Self, usize), sirius::SiriusError> {
+                        let allocated = ::core::cell::Cell::new(0);
+                        let config = &config.nested(&allocated)?;
+                        #deserialize
+                        #validate
+                        Ok((value, offset))
+                    }
+                }
+
+                #schema
+            };
+        }
 
-                match self {
-                    #(#serialize)*
+        quote! {
+            impl #impl_generics sirius::Sirius for #name #ty_generics #where_clause {
+                fn serialize(&self, output: &mut impl sirius::Writer) -> Result<usize, sirius::SiriusError> {
+                    self.serialize_with(output, &sirius::Config::default())
                 }
 
-                Ok(bytes_written)
+                fn deserialize(data: &[u8]) -> Result<(Self, usize), sirius::SiriusError> {
+                    Self::deserialize_with(data, &sirius::Config::default())
+                }
+
+                #[allow(unused_variables)]
+                fn serialize_with(
+                    &self,
+                    output: &mut impl sirius::Writer,
+                    config: &sirius::Config,
+                ) -> Result<usize, sirius::SiriusError> {
+                    #serialize
+                }
+
+                #[allow(unused_variables)]
+                fn encoded_len_with(&self, config: &sirius::Config) -> usize {
+                    #encoded_len
+                }
+
+                #[allow(unused_variables)]
+                fn deserialize_with(
+                    data: &[u8],
+                    config: &sirius::Config,
+                ) -> Result<(Self, usize), sirius::SiriusError> {
+                    let allocated = ::core::cell::Cell::new(0);
+                    let config = &config.nested(&allocated)?;
+                    #deserialize
+                    #validate
+                    Ok((value, offset))
+                }
+
+                sirius::__std! {
+                    fn deserialize_from(
+                        reader: &mut impl ::std::io::Read,
+                    ) -> Result<(Self, usize), sirius::SiriusError> {
+                        Self::deserialize_from_with(reader, &sirius::Config::default())
+                    }
+
+                    #[allow(unused_variables)]
+                    fn deserialize_from_with(
+                        reader: &mut impl ::std::io::Read,
+                        config: &sirius::Config,
+                    ) -> Result<(Self, usize), sirius::SiriusError> {
+                        let allocated = ::core::cell::Cell::new(0);
+                        let config = &config.nested(&allocated)?;
+                        #deserialize_from
+                        #validate
+                        Ok((value, offset))
+                    }
+                }
             }
 
-            fn deserialize(data: &[u8]) -> Result<(Self, usize), sirius::SiriusError> {
-                let mut offset = 0;
-                let (variant_index, shift) = <u8 as sirius::Sirius>::deserialize(data)?;
+            impl #impl_generics sirius::SiriusEncode for #name #ty_generics #where_clause {
+                fn encode_with(
+                    &self,
+                    output: &mut impl sirius::Writer,
+                    config: &sirius::Config,
+                ) -> Result<usize, sirius::SiriusError> {
+                    sirius::Sirius::serialize_with(self, output, config)
+                }
+
+                fn encode_len_with(&self, config: &sirius::Config) -> usize {
+                    sirius::Sirius::encoded_len_with(self, config)
+                }
+            }
 
-                offset += shift;
+            impl #de_impl_generics sirius::SiriusDe<'__de> for #name #ty_generics #where_clause {
+                fn deserialize_borrowed_with(
+                    data: &'__de [u8],
+                    config: &sirius::Config,
+                ) -> Result<(Self, usize), sirius::SiriusError> {
+                    <Self as sirius::Sirius>::deserialize_with(data, config)
+                }
+            }
 
-                Ok((
-                    match variant_index {
-                        #(#deserialize)*
+            #schema
+        }
+    }
+}
 
-                        _ => return Err(sirius::SiriusError::ParsingError {
-                            ty_name: stringify!(#name),
-                            error: format!("invalid variant index: {}", variant_index),
-                        }),
+/// # Validation
+///
+/// `#[sirius(validate = "path")]` calls `path(&value)` on a field right after it is read, or
+/// on the whole value once it is built when placed on the container. The function returns a
+/// `Result<(), E>` with `E: Display`, an error is returned as a `ValidationError` that
+/// `context` adds the field, e.g. `Range.start` or `Shape::Circle.0`, or the type to, the
+/// same way as to any other error of the value.
+fn validate_value(
+    validate: &syn::ExprPath,
+    binding: &proc_macro2::Ident,
+    context: &proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    quote! {
+        if let Err(error) = #validate(&#binding) {
+            return Err(
+                sirius::SiriusError::validation_error(sirius::__message!("{}", error)) #context
+            );
+        }
+    }
+}
+
+/// Returns `generics` with the bounds the generated impl needs added to its where-clause.
+///
+/// Unless overridden with `#[sirius(bound = "...")]`, every type parameter that appears in a
+/// serialized field's type gets a `T: sirius::Sirius` bound, or with `#[sirius(borrow)]` a
+/// `T: sirius::SiriusSchema + sirius::SiriusEncode + sirius::SiriusDe<'__de>` bound, where
+/// `'__de` is declared by every impl [`Methods::into_impls`] generates for the type.
+fn with_bounds<'a>(
+    generics: &syn::Generics,
+    attrs: &ContainerAttrs,
+    fields: impl Iterator<Item = &'a Field<'a>>,
+    borrowed: bool,
+) -> syn::Generics {
+    let mut generics = generics.clone();
+
+    let predicates: Vec<syn::WherePredicate> = match &attrs.bound {
+        Some(bound) => bound.iter().cloned().collect(),
+        None if attrs.into.is_some() && attrs.read_proxy().is_some() => vec![],
+        None => {
+            let field_types = fields
+                .filter(|field| field.attrs.needs_sirius())
+                .map(|field| &field.field.ty)
+                .collect::<Vec<_>>();
+
+            generics
+                .type_params()
+                .map(|param| &param.ident)
+                .filter(|param| field_types.iter().any(|ty| mentions_ident(ty, param)))
+                .map(|param| match borrowed {
+                    true => syn::parse_quote! {
+                        #param: sirius::SiriusSchema + sirius::SiriusEncode + sirius::SiriusDe<'__de>
                     },
-                    offset
-                ))
-            }
+                    false => syn::parse_quote! { #param: sirius::Sirius },
+                })
+                .collect()
         }
+    };
+
+    let proxies = [&attrs.into, &attrs.from, &attrs.try_from]
+        .into_iter()
+        .flatten()
+        .map(|proxy| -> syn::WherePredicate {
+            syn::parse_quote! { #proxy: sirius::Sirius }
+        });
+
+    generics
+        .make_where_clause()
+        .predicates
+        .extend(predicates.into_iter().chain(proxies));
+    generics
+}
+
+/// Whether `ident` appears anywhere in the tokens of `ty`.
+fn mentions_ident(ty: &syn::Type, ident: &syn::Ident) -> bool {
+    fn visit(tokens: proc_macro2::TokenStream, ident: &syn::Ident) -> bool {
+        tokens.into_iter().any(|token| match token {
+            proc_macro2::TokenTree::Ident(i) => i == *ident,
+            proc_macro2::TokenTree::Group(group) => visit(group.stream(), ident),
+            _ => false,
+        })
+    }
+
+    visit(quote! { #ty }, ident)
+}
+
+fn quote_option(value: Option<impl quote::ToTokens>) -> proc_macro2::TokenStream {
+    match value {
+        Some(value) => quote! { ::core::option::Option::Some(#value) },
+        None => quote! { ::core::option::Option::None },
     }
-    .into()
 }
 
 fn make_ident(string: &str) -> proc_macro2::Ident {
@@ -1,9 +1,14 @@
 use proc_macro::TokenStream;
 
+mod attr;
 mod derive;
+mod tag;
 
-#[proc_macro_derive(Sirius)]
+#[proc_macro_derive(Sirius, attributes(sirius))]
 pub fn sirius_derive(input: TokenStream) -> TokenStream {
-    let ast: syn::DeriveInput = syn::parse(input).unwrap();
+    let ast = syn::parse_macro_input!(input as syn::DeriveInput);
+
     derive::derive(&ast)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
 }
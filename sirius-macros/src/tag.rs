@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+
+use quote::quote;
+
+use crate::{
+    attr::{ContainerAttrs, VariantAttrs},
+    derive::Source,
+};
+
+/// How an enum's variant tags are written.
+#[derive(Clone, Copy, PartialEq)]
+pub enum TagType {
+    U8,
+    U16,
+    U32,
+
+    /// LEB128, see `sirius::varint`.
+    Varint,
+}
+
+impl TagType {
+    /// Fixed-width types the automatic selection picks from, smallest first.
+    const AUTO: [Self; 3] = [Self::U8, Self::U16, Self::U32];
+
+    pub fn from_name(name: &syn::LitStr) -> syn::Result<Self> {
+        match name.value().as_str() {
+            "u8" => Ok(Self::U8),
+            "u16" => Ok(Self::U16),
+            "u32" => Ok(Self::U32),
+            "varint" => Ok(Self::Varint),
+            _ => Err(syn::Error::new_spanned(
+                name,
+                "expected one of \"u8\", \"u16\", \"u32\" or \"varint\"",
+            )),
+        }
+    }
+
+    /// Reads the tag type from `#[repr(u8)]`, `#[repr(u16)]` or `#[repr(u32)]`.
+    pub fn from_repr(attrs: &[syn::Attribute]) -> syn::Result<Option<Self>> {
+        let mut tag_type = None;
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("repr")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("u8") {
+                    tag_type = Some(Self::U8);
+                } else if meta.path.is_ident("u16") {
+                    tag_type = Some(Self::U16);
+                } else if meta.path.is_ident("u32") {
+                    tag_type = Some(Self::U32);
+                } else if meta.input.peek(syn::token::Paren) {
+                    // `align(N)` and `packed(N)` carry arguments that are irrelevant to tags
+                    meta.input.parse::<proc_macro2::Group>()?;
+                }
+
+                Ok(())
+            })?;
+        }
+
+        Ok(tag_type)
+    }
+
+    fn bits(self) -> u32 {
+        match self {
+            Self::U8 => u8::BITS,
+            Self::U16 => u16::BITS,
+            Self::U32 => u32::BITS,
+            Self::Varint => u64::BITS,
+        }
+    }
+
+    fn max(self) -> u64 {
+        u64::MAX >> (u64::BITS - self.bits())
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::U8 => "u8",
+            Self::U16 => "u16",
+            Self::U32 => "u32",
+            Self::Varint => "varint",
+        }
+    }
+
+    /// Typed literal of `tag`, which must fit in this type.
+    pub fn literal(self, tag: u64) -> proc_macro2::Literal {
+        match self {
+            Self::U8 => proc_macro2::Literal::u8_suffixed(tag as u8),
+            Self::U16 => proc_macro2::Literal::u16_suffixed(tag as u16),
+            Self::U32 => proc_macro2::Literal::u32_suffixed(tag as u32),
+            Self::Varint => proc_macro2::Literal::u64_suffixed(tag),
+        }
+    }
+
+    /// Statement adding the bytes of `tag` written to `output` to `bytes_written`.
+    pub fn serialize(self, tag: u64) -> proc_macro2::TokenStream {
+        let tag = self.literal(tag);
+
+        match self {
+            Self::Varint => quote! {
+                bytes_written += sirius::varint::serialize(#tag, output)?;
+            },
+            _ => quote! {
+                bytes_written += sirius::Sirius::serialize_with(&#tag, output, config)?;
+            },
+        }
+    }
+
+    /// Statement adding the number of bytes of `tag` to `len`.
+    pub fn encoded_len(self, tag: u64) -> proc_macro2::TokenStream {
+        let tag = self.literal(tag);
+
+        match self {
+            Self::Varint => quote! { len += sirius::varint::encoded_len(#tag); },
+            _ => quote! { len += sirius::Sirius::encoded_len_with(&#tag, config); },
+        }
+    }
+
+    /// Expression reading a tag from `source`, evaluating to `Result<(tag, bytes_read), _>`.
+    pub fn deserialize(self, source: Source) -> proc_macro2::TokenStream {
+        let config = quote! { config };
+
+        match self {
+            Self::U8 => source.sirius(&syn::parse_quote! { u8 }, &config),
+            Self::U16 => source.sirius(&syn::parse_quote! { u16 }, &config),
+            Self::U32 => source.sirius(&syn::parse_quote! { u32 }, &config),
+            Self::Varint => source.varint(),
+        }
+    }
+
+    /// Expression of the matching `sirius::schema::TagType`.
+    pub fn schema(self) -> proc_macro2::TokenStream {
+        match self {
+            Self::U8 => quote! { sirius::schema::TagType::U8 },
+            Self::U16 => quote! { sirius::schema::TagType::U16 },
+            Self::U32 => quote! { sirius::schema::TagType::U32 },
+            Self::Varint => quote! { sirius::schema::TagType::Varint },
+        }
+    }
+
+    /// FNV-1a hash of `name`, xor-folded down to this type's width.
+    fn hash(self, name: &str) -> u64 {
+        let mut hash = name.bytes().fold(0x811c9dc5_u32, |hash, byte| {
+            (hash ^ byte as u32).wrapping_mul(0x01000193)
+        });
+
+        let mut folded = 0;
+        while hash != 0 {
+            folded ^= hash as u64 & self.max();
+            hash = hash.checked_shr(self.bits()).unwrap_or(0);
+        }
+
+        folded
+    }
+}
+
+/// Assigns a tag to every variant, in declaration order, and picks the type they are written as.
+///
+/// A variant uses, in order of precedence: its `#[sirius(tag = N)]`, its `= N` discriminant,
+/// the hash of its name under `#[sirius(hash_tags)]`, or the previous variant's tag plus one
+/// (`0` for the first variant), the same way Rust numbers implicit discriminants.
+///
+/// The tag type is `#[sirius(tag_type = "...")]`, else the enum's `#[repr(uN)]`, else the
+/// smallest of `u8`, `u16` and `u32` that fits the largest tag that is not a hash. Hashes are
+/// folded to the width of the tag type. The returned flag tells whether the type was picked
+/// automatically, which the enum's schema records so that users can see it and pin it.
+pub fn resolve(
+    attrs: &[syn::Attribute],
+    variants: &[&syn::Variant],
+    container: &ContainerAttrs,
+) -> syn::Result<(TagType, bool, Vec<u64>)> {
+    // `None` stands for a tag that is a hash of the variant name, resolved once the width is known
+    let mut tags = Vec::with_capacity(variants.len());
+    let mut next = Some(0_u64);
+
+    for variant in variants {
+        let attrs = VariantAttrs::parse(&variant.attrs)?;
+
+        let tag = if let Some(tag) = attrs.tag {
+            Some(tag.base10_parse::<u64>()?)
+        } else if let Some((_, discriminant)) = &variant.discriminant {
+            Some(parse_discriminant(discriminant)?)
+        } else if container.hash_tags {
+            None
+        } else {
+            Some(next.ok_or_else(|| {
+                syn::Error::new_spanned(
+                    &variant.ident,
+                    "tag of the previous variant is the largest possible tag, \
+                     set this one explicitly with `#[sirius(tag = N)]`",
+                )
+            })?)
+        };
+
+        next = tag.and_then(|tag| tag.checked_add(1));
+        tags.push(tag);
+    }
+
+    let explicit = container.tag_type.or(TagType::from_repr(attrs)?);
+    let tag_type = match explicit {
+        Some(tag_type) => tag_type,
+        None => {
+            let largest = tags.iter().flatten().copied().max().unwrap_or(0);
+            TagType::AUTO
+                .into_iter()
+                .find(|tag_type| largest <= tag_type.max())
+                .unwrap_or(TagType::U32)
+        }
+    };
+
+    let mut used = HashMap::new();
+    let tags = variants
+        .iter()
+        .zip(tags)
+        .map(|(variant, tag)| {
+            let tag = tag.unwrap_or_else(|| tag_type.hash(&variant.ident.to_string()));
+
+            if tag > tag_type.max() {
+                return Err(syn::Error::new_spanned(
+                    &variant.ident,
+                    format!(
+                        "tag {tag} of variant `{}` does not fit in `{}`",
+                        variant.ident,
+                        tag_type.name()
+                    ),
+                ));
+            }
+
+            if let Some(other) = used.insert(tag, &variant.ident) {
+                return Err(syn::Error::new_spanned(
+                    &variant.ident,
+                    format!(
+                        "tag {tag} of variant `{}` is already used by variant `{other}`",
+                        variant.ident
+                    ),
+                ));
+            }
+
+            Ok(tag)
+        })
+        .collect::<syn::Result<_>>()?;
+
+    Ok((tag_type, explicit.is_none(), tags))
+}
+
+fn parse_discriminant(discriminant: &syn::Expr) -> syn::Result<u64> {
+    match discriminant {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(int),
+            ..
+        }) => int.base10_parse(),
+        _ => Err(syn::Error::new_spanned(
+            discriminant,
+            "only integer literal discriminants can be used as tags, \
+             use `#[sirius(tag = N)]` instead",
+        )),
+    }
+}
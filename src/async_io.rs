@@ -0,0 +1,192 @@
+//! Framed [`Sirius`] values on `futures-io` [`AsyncRead`] and [`AsyncWrite`] streams.
+//!
+//! Frames have the layout of the [`frame`](crate::frame) module, so a value written by [`write_frame`] can be
+//! read by a blocking [`FrameReader`](crate::frame::FrameReader) and the other way around.
+
+use std::future::poll_fn;
+use std::io::{self, ErrorKind};
+use std::pin::Pin;
+
+use futures_io::{AsyncRead, AsyncWrite};
+
+use crate::frame::{CHUNK_SIZE, FrameFormat, LENGTH_BYTES};
+use crate::{Sirius, SiriusError};
+
+/// Write `value` as one frame to `writer` and return the number of bytes written, header
+/// included.
+///
+/// Nothing is written if the value does not fit in a frame. The writer is not flushed.
+pub async fn write_frame<T: Sirius>(
+    writer: &mut (impl AsyncWrite + Unpin),
+    value: &T,
+    format: FrameFormat,
+) -> Result<usize, SiriusError> {
+    let header_len = format.header_len();
+
+    let mut buffer = vec![0; header_len];
+    format.encode_payload(value, &mut buffer)?;
+
+    let (header, payload) = buffer.split_at_mut(header_len);
+    format.encode_header(payload.len(), header)?;
+    write_all(writer, &buffer).await?;
+
+    Ok(buffer.len())
+}
+
+/// Read the next frame from `reader` as a `T`, `None` if the stream ends cleanly before the
+/// next frame.
+///
+/// A frame that fails to decode or is too large is consumed whole, the error is returned and
+/// the next call reads the frame after it. Unlike a [`FrameReader`](crate::frame::FrameReader) nothing
+/// is kept between calls, so after a wrong magic byte the start of the next frame is unknown.
+pub async fn read_frame<T: Sirius>(
+    reader: &mut (impl AsyncRead + Unpin),
+    format: FrameFormat,
+) -> Result<Option<T>, SiriusError> {
+    let mut header = [0; 1 + LENGTH_BYTES];
+    let header = &mut header[..format.header_len()];
+
+    if read(reader, &mut header[..1]).await? == 0 {
+        return Ok(None);
+    }
+
+    // Checked before reading on so that the next frame is not partly consumed
+    format.check_magic(header[0])?;
+
+    read_exact(reader, &mut header[1..])
+        .await
+        .map_err(|error| error.advance(1))?;
+
+    let len = match format.decode_header(header) {
+        Ok(len) => len,
+        Err(error @ SiriusError::FrameTooLarge { len, .. }) => {
+            skip(reader, len).await?;
+            return Err(error);
+        }
+        Err(error) => return Err(error),
+    };
+
+    let mut payload = Vec::new();
+    while payload.len() < len {
+        let filled = payload.len();
+        payload.resize(len.min(filled + CHUNK_SIZE), 0);
+
+        let n = read(reader, &mut payload[filled..]).await?;
+        if n == 0 {
+            return Err(SiriusError::not_enough_data(len, filled).advance(header.len()));
+        }
+        payload.truncate(filled + n);
+    }
+
+    format.decode_payload(&payload, header.len()).map(Some)
+}
+
+async fn read(reader: &mut (impl AsyncRead + Unpin), buf: &mut [u8]) -> io::Result<usize> {
+    loop {
+        match poll_fn(|cx| Pin::new(&mut *reader).poll_read(cx, buf)).await {
+            Err(error) if error.kind() == ErrorKind::Interrupted => {}
+            result => return result,
+        }
+    }
+}
+
+/// Like [`read::read_exact`](crate::read::read_exact).
+async fn read_exact(
+    reader: &mut (impl AsyncRead + Unpin),
+    buf: &mut [u8],
+) -> Result<(), SiriusError> {
+    let mut filled = 0;
+
+    while filled < buf.len() {
+        match read(reader, &mut buf[filled..]).await? {
+            0 => return Err(SiriusError::not_enough_data(buf.len(), filled)),
+            n => filled += n,
+        }
+    }
+
+    Ok(())
+}
+
+/// Discard the next `len` bytes.
+async fn skip(reader: &mut (impl AsyncRead + Unpin), len: usize) -> Result<(), SiriusError> {
+    let mut buf = [0; CHUNK_SIZE];
+    let mut skipped = 0;
+
+    while skipped < len {
+        let chunk = (len - skipped).min(CHUNK_SIZE);
+        match read(reader, &mut buf[..chunk]).await? {
+            0 => return Err(SiriusError::not_enough_data(len, skipped)),
+            n => skipped += n,
+        }
+    }
+
+    Ok(())
+}
+
+async fn write_all(writer: &mut (impl AsyncWrite + Unpin), mut buf: &[u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        let result = poll_fn(|cx| Pin::new(&mut *writer).poll_write(cx, buf)).await;
+
+        match result {
+            Ok(0) => return Err(ErrorKind::WriteZero.into()),
+            Ok(n) => buf = &buf[n..],
+            Err(error) if error.kind() == ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_async_frame_roundtrip() {
+    use tokio_util::compat::TokioAsyncReadCompatExt;
+
+    let format = FrameFormat::new().magic(0xa5).max_frame_size(64);
+
+    // a small pipe, so that both sides have to wait on each other
+    let (client, server) = tokio::io::duplex(16);
+    let (mut client, mut server) = (client.compat(), server.compat());
+
+    let write = async {
+        assert_eq!(
+            write_frame(&mut client, &"hello".to_string(), format)
+                .await
+                .unwrap(),
+            1 + 4 + 9
+        );
+        // a payload that is not a valid `char`
+        write_frame(&mut client, &0xffu8, format).await.unwrap();
+        // a frame announcing too large a payload
+        write_all(&mut client, &[0xa5, 0, 0, 0, 100]).await.unwrap();
+        write_all(&mut client, &[0; 100]).await.unwrap();
+        write_frame(&mut client, &vec![1u16; 20], format)
+            .await
+            .unwrap();
+
+        drop(client);
+    };
+
+    let read = async {
+        assert_eq!(
+            read_frame::<String>(&mut server, format).await.unwrap(),
+            Some("hello".to_string())
+        );
+        assert!(matches!(
+            read_frame::<char>(&mut server, format).await,
+            Err(SiriusError::InvalidUtf8 { .. })
+        ));
+        assert!(matches!(
+            read_frame::<char>(&mut server, format).await,
+            Err(SiriusError::FrameTooLarge { len: 100, max: 64 })
+        ));
+        assert_eq!(
+            read_frame::<Vec<u16>>(&mut server, format).await.unwrap(),
+            Some(vec![1; 20])
+        );
+        assert_eq!(read_frame::<char>(&mut server, format).await.unwrap(), None);
+    };
+
+    tokio::join!(write, read);
+}
@@ -0,0 +1,470 @@
+//! Decoding values that borrow from the input instead of copying it.
+//!
+//! [`Sirius::deserialize`] returns values that own their data, so every `String` is a copy of
+//! bytes that are often still around. [`SiriusDe<'de>`] decodes from a `&'de [u8]` into values
+//! that may borrow from it, like `&'de str`, `&'de [u8]`, `Cow<'de, str>` and `Cow<'de, [u8]>`.
+//! Those cannot implement [`Sirius`], so they are written through [`SiriusEncode`]. Both traits
+//! are implemented for the built-in [`Sirius`] types, for `Vec`s, arrays, `Option`s and
+//! `Box`es of values that borrow, and by `#[derive(Sirius)]`, with `#[sirius(borrow)]` for
+//! types with lifetime parameters:
+//!
+//! ```
+//! use sirius::{Sirius, SiriusDe, SiriusEncode};
+//!
+//! #[derive(Sirius)]
+//! #[sirius(borrow)]
+//! struct Line<'a> {
+//!     level: u8,
+//!     message: &'a str,
+//! }
+//!
+//! let mut data = vec![];
+//! Line { level: 3, message: "disk full" }.encode(&mut data).unwrap();
+//!
+//! let (line, _) = Line::deserialize_borrowed(&data).unwrap();
+//! assert_eq!(line.message, "disk full");
+//! ```
+//!
+//! The encoding is the same as the owned types', a `&str` reads what a `String` wrote.
+//!
+//! Hand-written [`Sirius`] impls get both traits from
+//! [`impl_borrow_via_sirius!`](crate::impl_borrow_via_sirius).
+
+#[cfg(feature = "alloc")]
+use alloc::borrow::Cow;
+
+use crate::{
+    ByteCounter, Config, Sirius, SiriusError, SiriusSchema, Writer, impl_borrow_via_sirius,
+    impls::{
+        deserialize_array, deserialize_length, deserialize_option, length_len,
+        serialize_with_length_prefix,
+    },
+};
+#[cfg(feature = "alloc")]
+use crate::{
+    Utf8,
+    impls::{deserialize_box, deserialize_vec, serialize_length},
+    schema::{Schema, SchemaContext},
+};
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, string::String, vec::Vec};
+
+/// A trait for types that can be serialized into binary, including ones that borrow their data
+/// and thus cannot implement [`Sirius`].
+///
+/// Implemented for the built-in [`Sirius`] types, for `&str`, `&[u8]`, `Cow<str>` and
+/// `Cow<[u8]>`, for `Vec`s, arrays, `Option`s and `Box`es of those, and by `#[derive(Sirius)]`.
+pub trait SiriusEncode {
+    /// Same as [`encode`](SiriusEncode::encode), encoding the value as `config` says
+    fn encode_with(&self, output: &mut impl Writer, config: &Config) -> Result<usize, SiriusError>;
+
+    /// Write the serialized data to output and return the number of bytes written, like
+    /// [`Sirius::serialize`]
+    fn encode(&self, output: &mut impl Writer) -> Result<usize, SiriusError> {
+        self.encode_with(output, &Config::default())
+    }
+
+    /// Number of bytes [`encode`](SiriusEncode::encode) writes for the value, like
+    /// [`Sirius::encoded_len`]
+    fn encode_len(&self) -> usize {
+        self.encode_len_with(&Config::default())
+    }
+
+    /// Same as [`encode_len`](SiriusEncode::encode_len), for the value encoded as `config` says
+    fn encode_len_with(&self, config: &Config) -> usize {
+        let mut counter = ByteCounter::default();
+        _ = self.encode_with(&mut counter, config);
+        counter.0
+    }
+}
+
+/// A trait for types that can be deserialized from binary borrowed for `'de`, see the
+/// [module documentation](self).
+///
+/// Implemented for the built-in [`Sirius`] types, for `&str`, `&[u8]`, `Cow<str>` and
+/// `Cow<[u8]>`, for `Vec`s, arrays, `Option`s and `Box`es of those, and by `#[derive(Sirius)]`.
+pub trait SiriusDe<'de>: Sized {
+    /// Same as [`deserialize_borrowed`](SiriusDe::deserialize_borrowed), for data encoded as
+    /// `config` says
+    fn deserialize_borrowed_with(
+        data: &'de [u8],
+        config: &Config,
+    ) -> Result<(Self, usize), SiriusError>;
+
+    /// Deserialize the data, borrowing from it where possible, and return the number of bytes
+    /// read
+    fn deserialize_borrowed(data: &'de [u8]) -> Result<(Self, usize), SiriusError> {
+        Self::deserialize_borrowed_with(data, &Config::default())
+    }
+}
+
+impl_borrow_via_sirius! {
+    u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64, usize, isize, char,
+    {T: ?Sized} core::marker::PhantomData<T>,
+}
+
+#[cfg(feature = "alloc")]
+impl_borrow_via_sirius! {
+    String,
+    {T: Sirius} Box<[T]>,
+}
+
+#[cfg(feature = "alloc")]
+impl<T: SiriusEncode> SiriusEncode for Vec<T> {
+    fn encode_with(&self, output: &mut impl Writer, config: &Config) -> Result<usize, SiriusError> {
+        let prefix_len = serialize_length(self.len(), output, config)?;
+        Ok(prefix_len
+            + self
+                .iter()
+                .map(|item| item.encode_with(output, config))
+                .sum::<Result<usize, SiriusError>>()?)
+    }
+
+    fn encode_len_with(&self, config: &Config) -> usize {
+        length_len(self.len(), config)
+            + self
+                .iter()
+                .map(|item| item.encode_len_with(config))
+                .sum::<usize>()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'de, T: SiriusDe<'de>> SiriusDe<'de> for Vec<T> {
+    fn deserialize_borrowed_with(
+        data: &'de [u8],
+        config: &Config,
+    ) -> Result<(Self, usize), SiriusError> {
+        deserialize_vec(data, config, T::deserialize_borrowed_with)
+    }
+}
+
+impl<T: SiriusEncode, const N: usize> SiriusEncode for [T; N] {
+    fn encode_with(&self, output: &mut impl Writer, config: &Config) -> Result<usize, SiriusError> {
+        self.iter()
+            .map(|item| item.encode_with(output, config))
+            .sum::<Result<usize, SiriusError>>()
+    }
+
+    fn encode_len_with(&self, config: &Config) -> usize {
+        self.iter().map(|item| item.encode_len_with(config)).sum()
+    }
+}
+
+impl<'de, T: SiriusDe<'de>, const N: usize> SiriusDe<'de> for [T; N] {
+    fn deserialize_borrowed_with(
+        data: &'de [u8],
+        config: &Config,
+    ) -> Result<(Self, usize), SiriusError> {
+        deserialize_array(data, config, T::deserialize_borrowed_with)
+    }
+}
+
+impl<T: SiriusEncode> SiriusEncode for Option<T> {
+    fn encode_with(&self, output: &mut impl Writer, config: &Config) -> Result<usize, SiriusError> {
+        match self {
+            None => 0u8.serialize_with(output, config),
+            Some(value) => {
+                Ok(1u8.serialize_with(output, config)? + value.encode_with(output, config)?)
+            }
+        }
+    }
+
+    fn encode_len_with(&self, config: &Config) -> usize {
+        1 + self
+            .as_ref()
+            .map_or(0, |value| value.encode_len_with(config))
+    }
+}
+
+impl<'de, T: SiriusDe<'de>> SiriusDe<'de> for Option<T> {
+    fn deserialize_borrowed_with(
+        data: &'de [u8],
+        config: &Config,
+    ) -> Result<(Self, usize), SiriusError> {
+        deserialize_option(data, config, T::deserialize_borrowed_with)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: SiriusEncode> SiriusEncode for Box<T> {
+    fn encode_with(&self, output: &mut impl Writer, config: &Config) -> Result<usize, SiriusError> {
+        T::encode_with(self, output, config)
+    }
+
+    fn encode_len_with(&self, config: &Config) -> usize {
+        T::encode_len_with(self, config)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'de, T: SiriusDe<'de>> SiriusDe<'de> for Box<T> {
+    fn deserialize_borrowed_with(
+        data: &'de [u8],
+        config: &Config,
+    ) -> Result<(Self, usize), SiriusError> {
+        deserialize_box(data, config, T::deserialize_borrowed_with)
+    }
+}
+
+impl SiriusEncode for &str {
+    fn encode_with(&self, output: &mut impl Writer, config: &Config) -> Result<usize, SiriusError> {
+        serialize_with_length_prefix(self.as_bytes(), output, config)
+    }
+
+    fn encode_len_with(&self, config: &Config) -> usize {
+        length_len(self.len(), config) + self.len()
+    }
+}
+
+impl<'de: 'a, 'a> SiriusDe<'de> for &'a str {
+    fn deserialize_borrowed_with(
+        data: &'de [u8],
+        config: &Config,
+    ) -> Result<(Self, usize), SiriusError> {
+        let (bytes, bytes_read) = borrow_bytes(data, config, true)?;
+        let prefix_len = bytes_read - bytes.len();
+
+        if config.is_utf8_unchecked() {
+            // SAFETY: the caller of `Config::utf8_unchecked` promised that strings are UTF-8
+            return Ok((unsafe { core::str::from_utf8_unchecked(bytes) }, bytes_read));
+        }
+
+        match core::str::from_utf8(bytes) {
+            Ok(str) => Ok((str, bytes_read)),
+            Err(error) => Err(SiriusError::invalid_utf8(prefix_len + error.valid_up_to())),
+        }
+    }
+}
+
+impl SiriusSchema for &str {
+    #[cfg(feature = "alloc")]
+    fn describe(_context: &mut SchemaContext) -> Schema {
+        Schema::String
+    }
+}
+
+impl SiriusEncode for &[u8] {
+    fn encode_with(&self, output: &mut impl Writer, config: &Config) -> Result<usize, SiriusError> {
+        serialize_with_length_prefix(self, output, config)
+    }
+
+    fn encode_len_with(&self, config: &Config) -> usize {
+        length_len(self.len(), config) + self.len()
+    }
+}
+
+impl<'de: 'a, 'a> SiriusDe<'de> for &'a [u8] {
+    fn deserialize_borrowed_with(
+        data: &'de [u8],
+        config: &Config,
+    ) -> Result<(Self, usize), SiriusError> {
+        borrow_bytes(data, config, false)
+    }
+}
+
+impl SiriusSchema for &[u8] {
+    #[cfg(feature = "alloc")]
+    fn describe(context: &mut SchemaContext) -> Schema {
+        Schema::Sequence(Box::new(u8::describe(context)))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl SiriusEncode for Cow<'_, str> {
+    fn encode_with(&self, output: &mut impl Writer, config: &Config) -> Result<usize, SiriusError> {
+        serialize_with_length_prefix(self.as_bytes(), output, config)
+    }
+
+    fn encode_len_with(&self, config: &Config) -> usize {
+        length_len(self.len(), config) + self.len()
+    }
+}
+
+/// Decoded as `Cow::Borrowed`, unless [`Utf8::Lossy`] had to replace invalid UTF-8.
+#[cfg(feature = "alloc")]
+impl<'de: 'a, 'a> SiriusDe<'de> for Cow<'a, str> {
+    fn deserialize_borrowed_with(
+        data: &'de [u8],
+        config: &Config,
+    ) -> Result<(Self, usize), SiriusError> {
+        if config.utf8 == Utf8::Lossy && !config.is_utf8_unchecked() {
+            let (bytes, bytes_read) = borrow_bytes(data, config, true)?;
+            return Ok((String::from_utf8_lossy(bytes), bytes_read));
+        }
+
+        <&str>::deserialize_borrowed_with(data, config).map(|(str, l)| (Cow::Borrowed(str), l))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl SiriusSchema for Cow<'_, str> {
+    fn describe(context: &mut SchemaContext) -> Schema {
+        <&str>::describe(context)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl SiriusEncode for Cow<'_, [u8]> {
+    fn encode_with(&self, output: &mut impl Writer, config: &Config) -> Result<usize, SiriusError> {
+        serialize_with_length_prefix(self, output, config)
+    }
+
+    fn encode_len_with(&self, config: &Config) -> usize {
+        length_len(self.len(), config) + self.len()
+    }
+}
+
+/// Always decoded as `Cow::Borrowed`.
+#[cfg(feature = "alloc")]
+impl<'de: 'a, 'a> SiriusDe<'de> for Cow<'a, [u8]> {
+    fn deserialize_borrowed_with(
+        data: &'de [u8],
+        config: &Config,
+    ) -> Result<(Self, usize), SiriusError> {
+        <&[u8]>::deserialize_borrowed_with(data, config).map(|(bytes, l)| (Cow::Borrowed(bytes), l))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl SiriusSchema for Cow<'_, [u8]> {
+    fn describe(context: &mut SchemaContext) -> Schema {
+        <&[u8]>::describe(context)
+    }
+}
+
+/// Read a length prefix and return the bytes it announces, checked against the limit on the
+/// length of strings if `str`, else on the length of collections.
+fn borrow_bytes<'de>(
+    data: &'de [u8],
+    config: &Config,
+    str: bool,
+) -> Result<(&'de [u8], usize), SiriusError> {
+    let (len, prefix_len) = deserialize_length(data, config)?;
+    match str {
+        true => config.limits.check_str_len(len)?,
+        false => config.limits.check_len(len)?,
+    }
+
+    let end = prefix_len.checked_add(len).ok_or(SiriusError::Overflow)?;
+    let bytes = data
+        .get(prefix_len..end)
+        .ok_or_else(|| SiriusError::not_enough_data(end, data.len()))?;
+
+    Ok((bytes, end))
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_borrowed_sirius() {
+    use alloc::vec;
+
+    let mut data = vec![];
+    "borrowed".encode(&mut data).unwrap();
+    assert_eq!("borrowed".encode_len(), data.len());
+    assert_eq!(data, String::from("borrowed").serialize_buffered());
+
+    let (str, bytes_read) = <&str>::deserialize_borrowed(&data).unwrap();
+    assert_eq!((str, bytes_read), ("borrowed", data.len()));
+    assert!(core::ptr::eq(str.as_ptr(), data[4..].as_ptr()));
+
+    let (cow, _) = Cow::<str>::deserialize_borrowed(&data).unwrap();
+    assert!(matches!(cow, Cow::Borrowed("borrowed")));
+
+    let bytes = vec![1u8, 2, 3];
+    let mut data = vec![];
+    bytes.as_slice().encode(&mut data).unwrap();
+    assert_eq!(data, bytes.serialize_buffered());
+    assert_eq!(
+        <&[u8]>::deserialize_borrowed(&data).unwrap(),
+        (&bytes[..], 7)
+    );
+    assert_eq!(
+        Cow::<[u8]>::deserialize_borrowed(&data).unwrap().0,
+        Cow::<[u8]>::Owned(bytes)
+    );
+
+    // Owned types decode through the same trait
+    assert_eq!(u16::deserialize_borrowed(&[1, 2]).unwrap(), (258, 2));
+    assert_eq!(Vec::<u8>::deserialize_borrowed(&data).unwrap().0, [1, 2, 3]);
+
+    let config = Config::new().limits(crate::DecodeLimits::new().max_str_len(7));
+    let data = String::from("borrowed").serialize_buffered();
+    assert!(matches!(
+        <&str>::deserialize_borrowed_with(&data, &config),
+        Err(SiriusError::LimitExceeded {
+            limit: "max_str_len",
+            ..
+        })
+    ));
+    assert!(matches!(
+        <&str>::deserialize_borrowed(&[0, 0, 0, 2, 0xC3, 0x28]),
+        Err(SiriusError::InvalidUtf8 { offset: 4, .. })
+    ));
+
+    // `&str` cannot replace invalid UTF-8, `Cow<str>` can
+    let config = Config::new().utf8(Utf8::Lossy);
+    assert!(matches!(
+        <&str>::deserialize_borrowed_with(&[0, 0, 0, 2, 0xC3, 0x28], &config),
+        Err(SiriusError::InvalidUtf8 { offset: 4, .. })
+    ));
+    let (cow, _) =
+        Cow::<str>::deserialize_borrowed_with(&[0, 0, 0, 2, 0xC3, 0x28], &config).unwrap();
+    assert!(matches!(cow, Cow::Owned(ref str) if str == "\u{FFFD}("));
+    let (cow, _) = Cow::<str>::deserialize_borrowed_with(&data, &config).unwrap();
+    assert!(matches!(cow, Cow::Borrowed("borrowed")));
+
+    assert!(matches!(
+        <&[u8]>::deserialize_borrowed(&[0, 0, 0, 2, 1]),
+        Err(SiriusError::NotEnoughData { needed: 6, .. })
+    ));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_borrowed_containers() {
+    use alloc::{borrow::ToOwned, vec};
+
+    let strings = vec!["a", "bc"];
+    let mut data = vec![];
+    strings.encode(&mut data).unwrap();
+    assert_eq!(strings.encode_len(), data.len());
+    assert_eq!(
+        data,
+        vec!["a".to_owned(), "bc".to_owned()].serialize_buffered()
+    );
+
+    let (decoded, bytes_read) = Vec::<&str>::deserialize_borrowed(&data).unwrap();
+    assert_eq!((&decoded, bytes_read), (&strings, data.len()));
+    assert!(core::ptr::eq(decoded[1].as_ptr(), data[13..].as_ptr()));
+    assert!(matches!(
+        Vec::<&str>::deserialize_borrowed(&data[..12]),
+        Err(SiriusError::NotEnoughData { offset: 9, .. })
+    ));
+
+    let pair = [Some("x"), None];
+    let mut data = vec![];
+    pair.encode(&mut data).unwrap();
+    assert_eq!(data, [1, 0, 0, 0, 1, b'x', 0]);
+    assert_eq!(data, [Some("x".to_owned()), None].serialize_buffered());
+    assert_eq!(
+        <[Option<&str>; 2]>::deserialize_borrowed(&data).unwrap(),
+        (pair, 7)
+    );
+
+    let boxed = Box::new(&b"bytes"[..]);
+    let mut data = vec![];
+    boxed.encode(&mut data).unwrap();
+    assert_eq!(
+        Box::<&[u8]>::deserialize_borrowed(&data).unwrap(),
+        (boxed, 9)
+    );
+
+    assert!(matches!(
+        Option::<&str>::deserialize_borrowed(&[2]),
+        Err(SiriusError::ParsingError {
+            ty_name: "Option",
+            ..
+        })
+    ));
+}
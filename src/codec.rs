@@ -0,0 +1,247 @@
+//! A [`tokio_util::codec`] for framed [`Sirius`] values.
+//!
+//! Frames have the layout of the [`frame`](crate::frame) module, so a `Framed` stream using a [`SiriusCodec`]
+//! talks to a [`FrameWriter`](crate::frame::FrameWriter) or [`FrameReader`](crate::frame::FrameReader) on the
+//! other end.
+
+use std::marker::PhantomData;
+
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::frame::{CHUNK_SIZE, FrameFormat};
+use crate::{IoWriter, Sirius, SiriusError};
+
+/// Encodes and decodes `T`s as length-delimited frames.
+#[derive(Debug)]
+pub struct SiriusCodec<T> {
+    format: FrameFormat,
+
+    /// Bytes of a frame that was too large which have not arrived yet, and are dropped as they do.
+    skip: usize,
+
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> SiriusCodec<T> {
+    pub fn new() -> Self {
+        Self::with_format(FrameFormat::default())
+    }
+
+    pub fn with_format(format: FrameFormat) -> Self {
+        Self {
+            format,
+            skip: 0,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn format(&self) -> FrameFormat {
+        self.format
+    }
+}
+
+impl<T> Default for SiriusCodec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Sirius> Decoder for SiriusCodec<T> {
+    type Item = T;
+    type Error = SiriusError;
+
+    /// Decode the next frame in `src`.
+    ///
+    /// A frame that fails to decode is removed from `src` whole, a frame that is too large is
+    /// dropped as its bytes arrive, and after a wrong magic byte everything up to the next magic
+    /// byte is dropped, so that decoding can go on with the next frame after an error.
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<T>, SiriusError> {
+        if self.skip > 0 {
+            let skipped = self.skip.min(src.len());
+            src.advance(skipped);
+            self.skip -= skipped;
+
+            if self.skip > 0 {
+                return Ok(None);
+            }
+        }
+
+        let header_len = self.format.header_len();
+        if src.len() < header_len {
+            src.reserve(header_len - src.len());
+            return Ok(None);
+        }
+
+        let len = match self.format.decode_header(&src[..header_len]) {
+            Ok(len) => len,
+            Err(error @ SiriusError::FrameTooLarge { len, .. }) => {
+                src.advance(header_len);
+                self.skip = len;
+                return Err(error);
+            }
+            Err(error) => {
+                let next = src[1..]
+                    .iter()
+                    .position(|&byte| self.format.check_magic(byte).is_ok())
+                    .map_or(src.len(), |position| position + 1);
+                src.advance(next);
+                return Err(error);
+            }
+        };
+
+        if src.len() < header_len + len {
+            // the length comes from the peer, so room for the payload is made a chunk at a
+            // time as it arrives rather than all at once
+            src.reserve((header_len + len - src.len()).min(CHUNK_SIZE));
+            return Ok(None);
+        }
+
+        let frame = src.split_to(header_len + len);
+        self.format
+            .decode_payload(&frame[header_len..], header_len)
+            .map(Some)
+    }
+}
+
+impl<T: Sirius> Encoder<&T> for SiriusCodec<T> {
+    type Error = SiriusError;
+
+    /// Append `item` as one frame to `dst`, which is left as it was if the item does not fit in
+    /// a frame.
+    fn encode(&mut self, item: &T, dst: &mut BytesMut) -> Result<(), SiriusError> {
+        let start = dst.len();
+        let header_len = self.format.header_len();
+
+        dst.put_bytes(0, header_len);
+        let result = self
+            .format
+            .encode_payload(item, &mut IoWriter(BufMut::writer(&mut *dst)))
+            .and_then(|len| {
+                let header = &mut dst[start..start + header_len];
+                self.format.encode_header(len, header)
+            });
+
+        if result.is_err() {
+            dst.truncate(start);
+        }
+
+        result
+    }
+}
+
+impl<T: Sirius> Encoder<T> for SiriusCodec<T> {
+    type Error = SiriusError;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<(), SiriusError> {
+        Encoder::<&T>::encode(self, &item, dst)
+    }
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_codec_duplex() {
+    use futures::{SinkExt, StreamExt};
+    use tokio_util::codec::{FramedRead, FramedWrite};
+
+    let format = FrameFormat::new().magic(0xa5);
+
+    // a small pipe, so that frames arrive in pieces
+    let (client, server) = tokio::io::duplex(8);
+    let mut sink = FramedWrite::new(client, SiriusCodec::<Vec<String>>::with_format(format));
+    let mut stream = FramedRead::new(server, SiriusCodec::<Vec<String>>::with_format(format));
+
+    let messages = vec![
+        vec!["hello".to_string(), "world".to_string()],
+        vec![],
+        vec!["a".repeat(100)],
+    ];
+
+    let write = async {
+        for message in &messages {
+            sink.send(message).await.unwrap();
+        }
+        sink.send(vec!["owned".to_string()]).await.unwrap();
+
+        drop(sink);
+    };
+
+    let read = async {
+        let mut received = vec![];
+        while let Some(message) = stream.next().await {
+            received.push(message.unwrap());
+        }
+        received
+    };
+
+    let ((), received) = tokio::join!(write, read);
+    assert_eq!(received[..3], messages);
+    assert_eq!(received[3], ["owned"]);
+    assert_eq!(received.len(), 4);
+}
+
+#[test]
+fn test_codec_skips_corrupt_frames() {
+    let format = FrameFormat::new().magic(0xa5).max_frame_size(8);
+    let mut codec = SiriusCodec::<char>::with_format(format);
+
+    let mut data = BytesMut::new();
+    codec.encode(&'a', &mut data).unwrap();
+    // garbage in front of a frame, and a frame announcing too large a payload
+    data.extend([0x00, 0x13]);
+    data.extend([0xa5, 0, 0, 0, 10]);
+    data.extend([0; 10]);
+    // a payload that is not a valid `char`
+    data.extend([0xa5, 0, 0, 0, 1, 0xff]);
+    codec.encode('b', &mut data).unwrap();
+
+    // every byte on its own, as if they arrived one at a time
+    let mut src = BytesMut::new();
+    let mut results = vec![];
+    for byte in data {
+        src.put_u8(byte);
+        loop {
+            match codec.decode(&mut src) {
+                Ok(None) => break,
+                result => results.push(result),
+            }
+        }
+    }
+
+    assert!(matches!(results[0], Ok(Some('a'))));
+    assert!(matches!(
+        results[1],
+        Err(SiriusError::ParsingError {
+            ty_name: "frame",
+            ..
+        })
+    ));
+    assert!(matches!(
+        results[2],
+        Err(SiriusError::FrameTooLarge { len: 10, max: 8 })
+    ));
+    assert!(matches!(results[3], Err(SiriusError::InvalidUtf8 { .. })));
+    assert!(matches!(results[4], Ok(Some('b'))));
+    assert_eq!(results.len(), 5);
+    assert!(src.is_empty());
+}
+
+#[test]
+fn test_codec_huge_length() {
+    let mut codec = SiriusCodec::<Vec<u8>>::new();
+
+    // a header declaring a payload of almost 4 GiB, of which only a few bytes arrive
+    let mut src = BytesMut::new();
+    src.extend([0xff, 0xff, 0xff, 0xff, 1, 2, 3]);
+    assert!(matches!(codec.decode(&mut src), Ok(None)));
+    assert!(src.capacity() <= 2 * CHUNK_SIZE);
+
+    let mut codec = SiriusCodec::<Vec<u8>>::with_format(FrameFormat::new().max_frame_size(16));
+    let mut src = BytesMut::new();
+    src.extend([0xff, 0xff, 0xff, 0xff]);
+    assert!(matches!(
+        codec.decode(&mut src),
+        Err(SiriusError::FrameTooLarge { max: 16, .. })
+    ));
+    assert!(src.capacity() <= 2 * CHUNK_SIZE);
+}
@@ -0,0 +1,300 @@
+//! Options for how values are encoded, see [`Sirius::serialize_with`].
+//!
+//! [`Sirius::serialize_with`]: crate::Sirius::serialize_with
+
+use core::cell::Cell;
+
+use crate::SiriusError;
+
+/// How values are encoded, which has to be the same when writing and reading them.
+///
+/// Built with [`Config::new`] and the builder methods, e.g.
+/// `Config::new().byte_order(ByteOrder::Little)`. The default is the encoding of
+/// [`Sirius::serialize`](crate::Sirius::serialize).
+///
+/// While a value is decoded, the config also tracks how deep in the value the decoding is and
+/// how many bytes it allocated, to enforce the [`DecodeLimits`]. `'a` is the lifetime of that
+/// tracking, configs built with [`Config::new`] are `Config<'static>`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Config<'a> {
+    /// Byte order of numbers, length prefixes and enum tags.
+    pub byte_order: ByteOrder,
+
+    /// How integers, length prefixes and enum tags are written.
+    pub int_encoding: IntEncoding,
+
+    /// Type of the length prefixes of strings and sequences.
+    pub length_prefix: LengthPrefix,
+
+    /// Limits on what decoding untrusted data may cost.
+    pub limits: DecodeLimits,
+
+    /// What decoding does with strings that are not valid UTF-8.
+    pub utf8: Utf8,
+
+    /// Strings are trusted to be valid UTF-8, see [`Config::utf8_unchecked`].
+    utf8_unchecked: bool,
+
+    /// Nesting depth of the value being decoded, see [`Config::nested`].
+    depth: usize,
+
+    /// Bytes allocated while decoding the outermost value, see [`Config::allocate`].
+    allocated: Option<&'a Cell<usize>>,
+}
+
+impl<'a> Config<'a> {
+    pub const fn new() -> Self {
+        Self {
+            byte_order: ByteOrder::Big,
+            int_encoding: IntEncoding::Fixed,
+            length_prefix: LengthPrefix::U32,
+            limits: DecodeLimits::new(),
+            utf8: Utf8::Strict,
+            utf8_unchecked: false,
+            depth: 0,
+            allocated: None,
+        }
+    }
+
+    pub const fn byte_order(mut self, byte_order: ByteOrder) -> Self {
+        self.byte_order = byte_order;
+        self
+    }
+
+    pub const fn int_encoding(mut self, int_encoding: IntEncoding) -> Self {
+        self.int_encoding = int_encoding;
+        self
+    }
+
+    pub const fn length_prefix(mut self, length_prefix: LengthPrefix) -> Self {
+        self.length_prefix = length_prefix;
+        self
+    }
+
+    pub const fn limits(mut self, limits: DecodeLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub const fn utf8(mut self, utf8: Utf8) -> Self {
+        self.utf8 = utf8;
+        self
+    }
+
+    /// Skip checking that decoded strings are valid UTF-8, for data from trusted sources.
+    /// Overrides [`Config::utf8`].
+    ///
+    /// # Safety
+    ///
+    /// Every string decoded with the config must be valid UTF-8, else decoding creates a
+    /// `String` or `&str` that is not, which is undefined behavior.
+    pub const unsafe fn utf8_unchecked(mut self) -> Self {
+        self.utf8_unchecked = true;
+        self
+    }
+
+    /// Whether strings are trusted to be valid UTF-8, see [`Config::utf8_unchecked`].
+    pub const fn is_utf8_unchecked(&self) -> bool {
+        self.utf8_unchecked
+    }
+
+    /// Config to decode a value nested in the one decoded with `self` with, e.g. a field or an
+    /// element, failing once that is deeper than [`DecodeLimits::max_depth`].
+    ///
+    /// `allocated` starts counting allocations for [`allocate`](Self::allocate) if `self` does
+    /// not count them yet, so that the nested values share one count. Every impl that decodes
+    /// nested values goes through this:
+    ///
+    /// ```ignore
+    /// let allocated = Cell::new(0);
+    /// let config = &config.nested(&allocated)?;
+    /// ```
+    pub fn nested<'b>(&self, allocated: &'b Cell<usize>) -> Result<Config<'b>, SiriusError>
+    where
+        'a: 'b,
+    {
+        let depth = self.depth + 1;
+        if depth > self.limits.max_depth {
+            return Err(SiriusError::limit_exceeded(
+                "max_depth",
+                depth,
+                self.limits.max_depth,
+            ));
+        }
+
+        Ok(Config {
+            depth,
+            allocated: Some(self.allocated.unwrap_or(allocated)),
+            ..*self
+        })
+    }
+
+    /// Count `bytes` allocated while decoding, failing once the value being decoded allocated
+    /// more than [`DecodeLimits::max_alloc`].
+    pub fn allocate(&self, bytes: usize) -> Result<(), SiriusError> {
+        let total = self.allocated.map_or(0, Cell::get).saturating_add(bytes);
+        if total > self.limits.max_alloc {
+            return Err(SiriusError::limit_exceeded(
+                "max_alloc",
+                total,
+                self.limits.max_alloc,
+            ));
+        }
+
+        if let Some(allocated) = self.allocated {
+            allocated.set(total);
+        }
+
+        Ok(())
+    }
+}
+
+/// Limits on what decoding a value may cost, exceeding them is a
+/// [`SiriusError::LimitExceeded`].
+///
+/// Lengths are checked as soon as they are read, before anything is allocated for them. The
+/// default is no limits, data from untrusted sources should set them, e.g.
+/// `DecodeLimits::new().max_alloc(1 << 20).max_depth(64)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DecodeLimits {
+    /// Total bytes the value may allocate, for its collections, strings and boxes.
+    pub max_alloc: usize,
+
+    /// Number of elements of every collection.
+    pub max_len: usize,
+
+    /// Nesting depth of collections, boxes, structs and enums.
+    pub max_depth: usize,
+
+    /// Length of every string, in bytes.
+    pub max_str_len: usize,
+}
+
+impl DecodeLimits {
+    pub const fn new() -> Self {
+        Self {
+            max_alloc: usize::MAX,
+            max_len: usize::MAX,
+            max_depth: usize::MAX,
+            max_str_len: usize::MAX,
+        }
+    }
+
+    pub const fn max_alloc(mut self, max_alloc: usize) -> Self {
+        self.max_alloc = max_alloc;
+        self
+    }
+
+    pub const fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    pub const fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub const fn max_str_len(mut self, max_str_len: usize) -> Self {
+        self.max_str_len = max_str_len;
+        self
+    }
+
+    /// Fail if a collection of `len` elements is longer than [`max_len`](Self::max_len).
+    pub fn check_len(&self, len: usize) -> Result<(), SiriusError> {
+        match len > self.max_len {
+            true => Err(SiriusError::limit_exceeded("max_len", len, self.max_len)),
+            false => Ok(()),
+        }
+    }
+
+    /// Fail if a string of `len` bytes is longer than [`max_str_len`](Self::max_str_len).
+    pub fn check_str_len(&self, len: usize) -> Result<(), SiriusError> {
+        match len > self.max_str_len {
+            true => Err(SiriusError::limit_exceeded(
+                "max_str_len",
+                len,
+                self.max_str_len,
+            )),
+            false => Ok(()),
+        }
+    }
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What decoding does with strings that are not valid UTF-8.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Utf8 {
+    /// Fail with [`SiriusError::InvalidUtf8`].
+    #[default]
+    Strict,
+
+    /// Replace invalid sequences with `U+FFFD REPLACEMENT CHARACTER`, like
+    /// `String::from_utf8_lossy`, e.g. for logs. Strings decoded as `&str` cannot be
+    /// replaced and fail like with [`Utf8::Strict`].
+    Lossy,
+}
+
+/// Order of the bytes of fixed-size numbers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ByteOrder {
+    /// Most significant byte first.
+    #[default]
+    Big,
+
+    /// Least significant byte first, the order of most file formats and of x86 and ARM.
+    Little,
+
+    /// Order of the target the code is built for, for data that does not leave the machine.
+    Native,
+}
+
+impl ByteOrder {
+    /// Whether the most significant byte comes first.
+    pub const fn is_big_endian(self) -> bool {
+        match self {
+            Self::Big => true,
+            Self::Little => false,
+            Self::Native => cfg!(target_endian = "big"),
+        }
+    }
+}
+
+/// How integers, length prefixes and enum tags are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IntEncoding {
+    /// With the size of their type, in the configured [`ByteOrder`].
+    #[default]
+    Fixed,
+
+    /// As [LEB128 varints](crate::varint), signed integers zigzag-encoded, so that numbers close
+    /// to zero take a single byte. `u8`, `i8`, floats and `u8` enum tags keep their fixed size.
+    Varint,
+}
+
+/// Integer type the lengths of strings and sequences are written as.
+///
+/// Values longer than the type can count are rejected with [`SiriusError::Overflow`], when
+/// writing them and when reading a length that does not fit in a `usize`. Under
+/// [`IntEncoding::Varint`] the `U16`, `U32` and `U64` prefixes are varints as well, which can
+/// count up to the maximum of their type.
+///
+/// [`SiriusError::Overflow`]: crate::SiriusError::Overflow
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LengthPrefix {
+    U8,
+    U16,
+    #[default]
+    U32,
+    U64,
+
+    /// A [LEB128 varint](crate::varint), whatever the [`IntEncoding`].
+    Varint,
+}
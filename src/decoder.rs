@@ -0,0 +1,827 @@
+//! Push-style decoding of values that arrive in chunks, e.g. from non-blocking sockets.
+//!
+//! A [`Decoder`] buffers the chunks it is fed and follows the wire layout of `T` described by
+//! its [`Schema`] to find where the value ends, picking up where it stopped on every call. The
+//! value is deserialized once, when all of its bytes are there.
+//!
+//! Types whose schema contains [`Schema::Opaque`] parts cannot be followed. For those the
+//! decoder retries [`Sirius::deserialize`] once the bytes asked for by its last
+//! [`SiriusError::NotEnoughData`] have arrived.
+
+use alloc::{vec, vec::Vec};
+use core::marker::PhantomData;
+
+use crate::{
+    Config, IntEncoding, LengthPrefix, Sirius, SiriusError, impls,
+    schema::{FieldSchema, Primitive, Schema, TagType},
+    varint,
+};
+
+/// Result of feeding a chunk to a [`Decoder`].
+#[derive(Debug, PartialEq)]
+pub enum Decoded<T> {
+    /// The value is not complete, at least `at_least` more bytes are needed before the decoder
+    /// can make progress.
+    NeedMore { at_least: usize },
+
+    /// The value is complete. The second field is the number of bytes of the last chunk that
+    /// belong to it, the rest of the chunk is not part of the value.
+    Complete(T, usize),
+}
+
+/// Decodes one `T` at a time from chunks of input, see the [module docs](self).
+///
+/// After [`Decoded::Complete`] or an error the decoder starts over with the next value.
+pub struct Decoder<T> {
+    config: Config<'static>,
+    buffer: Vec<u8>,
+    scanner: Option<Scanner>,
+
+    /// Buffer length at which retrying `deserialize` is worth it, when there is no scanner.
+    wanted: usize,
+
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Sirius> Decoder<T> {
+    pub fn new() -> Self {
+        Self::with_config(Config::default())
+    }
+
+    /// Decoder for values encoded as `config` says.
+    pub fn with_config(config: Config<'static>) -> Self {
+        Self {
+            config,
+            buffer: vec![],
+            scanner: Scanner::new(&T::schema(), &config),
+            wanted: 0,
+            marker: PhantomData,
+        }
+    }
+
+    /// Feed the next chunk of input.
+    pub fn decode(&mut self, chunk: &[u8]) -> Result<Decoded<T>, SiriusError> {
+        let start = self.buffer.len();
+        self.buffer.extend_from_slice(chunk);
+
+        let result = self.advance(start);
+        if !matches!(result, Ok(Decoded::NeedMore { .. })) {
+            self.reset();
+        }
+
+        result
+    }
+
+    /// Number of bytes of the current value received so far.
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Drop the bytes of the current value and start over.
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.wanted = 0;
+
+        if let Some(scanner) = &mut self.scanner {
+            scanner.reset();
+        }
+    }
+
+    fn advance(&mut self, start: usize) -> Result<Decoded<T>, SiriusError> {
+        if let Some(scanner) = &mut self.scanner {
+            match scanner.scan(&self.buffer) {
+                Scan::NeedMore(at_least) => return Ok(Decoded::NeedMore { at_least }),
+
+                // Invalid data is left to `deserialize` to report
+                Scan::Complete | Scan::Invalid => {}
+            }
+        }
+
+        if self.buffer.len() < self.wanted {
+            return Ok(Decoded::NeedMore {
+                at_least: self.wanted - self.buffer.len(),
+            });
+        }
+
+        match T::deserialize_with(&self.buffer, &self.config) {
+            Ok((value, len)) => Ok(Decoded::Complete(value, len.saturating_sub(start))),
+            Err(SiriusError::NotEnoughData { offset, needed, .. })
+                if offset + needed > self.buffer.len() =>
+            {
+                // The schema did not match what `deserialize` reads, stop following it
+                self.scanner = None;
+                self.wanted = offset + needed;
+
+                Ok(Decoded::NeedMore {
+                    at_least: self.wanted - self.buffer.len(),
+                })
+            }
+            Err(error) => Err(error),
+        }
+    }
+}
+
+impl<T: Sirius> Default for Decoder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// [`Schema`] of a type flattened into nodes referring to each other by index, so that
+/// recursive types and the scanner's position can be stored without borrowing the schema.
+enum Node {
+    Fixed(usize),
+    Number(Primitive),
+    Char,
+    Bytes,
+    Sequence {
+        element: usize,
+
+        /// Lower bound of the in-memory size of an element, see [`Scanner::size`].
+        element_size: usize,
+    },
+    Array {
+        len: usize,
+        element: usize,
+    },
+    Struct {
+        versioned: bool,
+        fields: usize,
+    },
+    Enum {
+        tag_type: TagType,
+
+        /// Tag and field list of every variant.
+        variants: Vec<(u64, usize)>,
+    },
+}
+
+/// A field of a struct or variant: its node, the versions it is part of and how it overrides
+/// the [`Config`].
+struct FieldNode {
+    node: usize,
+    since: Option<u32>,
+    until: Option<u32>,
+    varint: bool,
+    length_prefix: Option<LengthPrefix>,
+}
+
+impl FieldNode {
+    /// Configuration the field is written with, inside a value written with `config`.
+    fn config(&self, mut config: Config<'static>) -> Config<'static> {
+        if self.varint {
+            config = config.int_encoding(IntEncoding::Varint);
+        }
+
+        if let Some(length_prefix) = self.length_prefix {
+            config = config.length_prefix(length_prefix);
+        }
+
+        config
+    }
+}
+
+/// Tasks carry the [`Config`] of their part of the value, which fields can override.
+enum Task {
+    /// Scan one value of the node.
+    Value(usize, Config<'static>),
+
+    /// Scan the fields of a field list from field `next` on, skipping the ones that are not
+    /// part of `version`.
+    Fields {
+        fields: usize,
+        next: usize,
+        version: Option<u64>,
+        config: Config<'static>,
+    },
+
+    /// Scan `remaining` more values of `element`.
+    Elements {
+        element: usize,
+        remaining: u64,
+        config: Config<'static>,
+    },
+}
+
+enum Scan {
+    /// At least this many more bytes are needed.
+    NeedMore(usize),
+
+    Complete,
+
+    /// The data does not follow the schema.
+    Invalid,
+}
+
+/// Follows the wire layout of a value through a growing buffer, see [`Decoder`].
+struct Scanner {
+    config: Config<'static>,
+    nodes: Vec<Node>,
+    field_lists: Vec<Vec<FieldNode>>,
+
+    /// Position in the buffer up to which the value has been scanned.
+    position: usize,
+    tasks: Vec<Task>,
+
+    /// Bytes the strings and sequences scanned so far allocate at least, counted against
+    /// [`DecodeLimits::max_alloc`].
+    ///
+    /// [`DecodeLimits::max_alloc`]: crate::DecodeLimits::max_alloc
+    allocated: usize,
+}
+
+impl Scanner {
+    /// Returns `None` for schemas with opaque parts.
+    fn new(schema: &Schema, config: &Config<'static>) -> Option<Self> {
+        let mut scanner = Self {
+            config: *config,
+            nodes: vec![],
+            field_lists: vec![],
+            position: 0,
+            tasks: vec![],
+            allocated: 0,
+        };
+
+        let root = scanner.add(schema, &mut vec![])?;
+        debug_assert_eq!(root, 0);
+
+        for idx in 0..scanner.nodes.len() {
+            if let Node::Sequence { element, .. } = scanner.nodes[idx] {
+                let size = scanner.size(element, &mut vec![]);
+                scanner.nodes[idx] = Node::Sequence {
+                    element,
+                    element_size: size,
+                };
+            }
+        }
+        scanner.reset();
+
+        Some(scanner)
+    }
+
+    fn reset(&mut self) {
+        self.position = 0;
+        self.allocated = 0;
+        self.tasks.clear();
+        self.tasks.push(Task::Value(0, self.config));
+    }
+
+    /// Adds the nodes of `schema` and returns the index of its root. `named` holds the structs
+    /// and enums being added, for [`Schema::Recursive`].
+    fn add(&mut self, schema: &Schema, named: &mut Vec<(&'static str, usize)>) -> Option<usize> {
+        let node = match schema {
+            Schema::Primitive(primitive) => Node::Number(*primitive),
+            Schema::Char => Node::Char,
+            Schema::String => Node::Bytes,
+            Schema::Unit => Node::Fixed(0),
+            Schema::Opaque(_) => return None,
+            Schema::Recursive(name) => {
+                let (_, idx) = named.iter().rev().find(|(named, _)| named == name)?;
+                return Some(*idx);
+            }
+            Schema::Sequence(element) => {
+                let idx = self.reserve();
+                let element = self.add(element, named)?;
+                self.nodes[idx] = Node::Sequence {
+                    element,
+                    element_size: 0,
+                };
+                return Some(idx);
+            }
+            Schema::Array { len, element } => {
+                let idx = self.reserve();
+                let element = self.add(element, named)?;
+                self.nodes[idx] = Node::Array { len: *len, element };
+                return Some(idx);
+            }
+            Schema::Struct(schema) => {
+                let idx = self.reserve();
+                named.push((schema.name, idx));
+                let fields = self.add_fields(&schema.fields, named);
+                named.pop();
+
+                self.nodes[idx] = Node::Struct {
+                    versioned: schema.version.is_some(),
+                    fields: fields?,
+                };
+                return Some(idx);
+            }
+            Schema::Enum(schema) => {
+                let idx = self.reserve();
+                named.push((schema.name, idx));
+                let variants = schema
+                    .variants
+                    .iter()
+                    .map(|variant| Some((variant.tag, self.add_fields(&variant.fields, named)?)))
+                    .collect::<Option<_>>();
+                named.pop();
+
+                self.nodes[idx] = Node::Enum {
+                    tag_type: schema.tag_type,
+                    variants: variants?,
+                };
+                return Some(idx);
+            }
+        };
+
+        self.nodes.push(node);
+        Some(self.nodes.len() - 1)
+    }
+
+    fn add_fields(
+        &mut self,
+        fields: &[FieldSchema],
+        named: &mut Vec<(&'static str, usize)>,
+    ) -> Option<usize> {
+        let fields = fields
+            .iter()
+            .map(|field| {
+                Some(FieldNode {
+                    node: self.add(&field.schema, named)?,
+                    since: field.since,
+                    until: field.until,
+                    varint: field.varint,
+                    length_prefix: field.length_prefix,
+                })
+            })
+            .collect::<Option<_>>()?;
+
+        self.field_lists.push(fields);
+        Some(self.field_lists.len() - 1)
+    }
+
+    /// Lower bound of the in-memory size of a value of `node`, for counting sequences against
+    /// [`DecodeLimits::max_alloc`](crate::DecodeLimits::max_alloc) like `deserialize` does
+    /// with their element type's size. Numbers take their size, other values at least the sum
+    /// of their parts, and the values of `visiting` nodes, which contain themselves, nothing.
+    fn size(&self, node: usize, visiting: &mut Vec<usize>) -> usize {
+        if visiting.contains(&node) {
+            return 0;
+        }
+
+        visiting.push(node);
+        let fields_size = |fields: usize, visiting: &mut Vec<usize>| -> usize {
+            self.field_lists[fields]
+                .iter()
+                .map(|field| self.size(field.node, visiting))
+                .sum()
+        };
+
+        let size = match &self.nodes[node] {
+            Node::Fixed(_) => 0,
+            Node::Number(primitive) => primitive.size(),
+            Node::Char => size_of::<char>(),
+            // A `Box<str>` or `Box<[T]>` is a pointer and a length
+            Node::Bytes | Node::Sequence { .. } => 2 * size_of::<usize>(),
+            Node::Array { len, element } => len.saturating_mul(self.size(*element, visiting)),
+            Node::Struct { fields, .. } => fields_size(*fields, visiting),
+            Node::Enum { variants, .. } => variants
+                .iter()
+                .map(|(_, fields)| fields_size(*fields, visiting))
+                .max()
+                .unwrap_or(0),
+        };
+        visiting.pop();
+
+        size
+    }
+
+    /// Pushes a placeholder for a node whose children have to be added first.
+    fn reserve(&mut self) -> usize {
+        self.nodes.push(Node::Fixed(0));
+        self.nodes.len() - 1
+    }
+
+    /// Scan as much of `data` as possible, starting from where the last call stopped.
+    ///
+    /// Lengths are checked against the [`DecodeLimits`](crate::DecodeLimits) as they are read,
+    /// like `deserialize` does, so that a length over a limit does not make the decoder wait
+    /// for, and buffer, that many bytes. The data is [`Scan::Invalid`] then, and
+    /// `deserialize` reports the exceeded limit.
+    fn scan(&mut self, data: &[u8]) -> Scan {
+        while let Some(task) = self.tasks.pop() {
+            let rest = &data[self.position..];
+
+            // Bytes the task allocates, counted once it is done as it may be retried
+            let mut allocate = 0;
+
+            // Number of bytes the task consumes, or how many more it needs to make progress
+            let step = match task {
+                Task::Value(node, config) => match &self.nodes[node] {
+                    Node::Fixed(size) => Ok(*size),
+                    Node::Number(primitive) => match is_varint(*primitive, &config) {
+                        true => varint_len(rest),
+                        false => Ok(primitive.size()),
+                    },
+                    Node::Char => Ok(rest.first().map_or(1, |&byte| impls::utf8_len(byte))),
+                    Node::Bytes => read_length(rest, &config).and_then(|(len, prefix_len)| {
+                        let len = usize::try_from(len).map_err(|_| None)?;
+                        config.limits.check_str_len(len).map_err(|_| None)?;
+                        if self.allocated.saturating_add(len) > config.limits.max_alloc {
+                            return Err(None);
+                        }
+
+                        allocate = len;
+                        len.checked_add(prefix_len).ok_or(None)
+                    }),
+                    Node::Sequence {
+                        element,
+                        element_size,
+                    } => read_length(rest, &config).and_then(|(remaining, prefix_len)| {
+                        let len = usize::try_from(remaining).map_err(|_| None)?;
+                        config.limits.check_len(len).map_err(|_| None)?;
+
+                        let bytes = len.saturating_mul(*element_size);
+                        if self.allocated.saturating_add(bytes) > config.limits.max_alloc {
+                            return Err(None);
+                        }
+
+                        allocate = bytes;
+
+                        self.tasks.push(Task::Elements {
+                            element: *element,
+                            remaining,
+                            config,
+                        });
+                        Ok(prefix_len)
+                    }),
+                    Node::Array { len, element } => {
+                        self.tasks.push(Task::Elements {
+                            element: *element,
+                            remaining: *len as u64,
+                            config,
+                        });
+                        Ok(0)
+                    }
+                    Node::Struct { versioned, fields } => {
+                        let version = match versioned {
+                            true => read_varint(rest).map(|(version, len)| (Some(version), len)),
+                            false => Ok((None, 0)),
+                        };
+
+                        version.map(|(version, len)| {
+                            self.tasks.push(Task::Fields {
+                                fields: *fields,
+                                next: 0,
+                                version,
+                                config,
+                            });
+                            len
+                        })
+                    }
+                    Node::Enum { tag_type, variants } => read_tag(rest, *tag_type, &config)
+                        .and_then(|(tag, len)| {
+                            let (_, fields) =
+                                variants.iter().find(|(t, _)| *t == tag).ok_or(None)?;
+                            self.tasks.push(Task::Fields {
+                                fields: *fields,
+                                next: 0,
+                                version: None,
+                                config,
+                            });
+                            Ok(len)
+                        }),
+                },
+                Task::Fields {
+                    fields,
+                    next,
+                    version,
+                    config,
+                } => {
+                    let present = |field: &FieldNode| {
+                        version.is_none_or(|version| {
+                            field.since.is_none_or(|since| since as u64 <= version)
+                                && field.until.is_none_or(|until| version < until as u64)
+                        })
+                    };
+
+                    let list = &self.field_lists[fields];
+                    if let Some(idx) = (next..list.len()).find(|&idx| present(&list[idx])) {
+                        self.tasks.push(Task::Fields {
+                            fields,
+                            next: idx + 1,
+                            version,
+                            config,
+                        });
+                        self.tasks
+                            .push(Task::Value(list[idx].node, list[idx].config(config)));
+                    }
+                    Ok(0)
+                }
+                Task::Elements {
+                    element,
+                    remaining,
+                    config,
+                } => {
+                    if let Node::Fixed(size) = self.nodes[element] {
+                        // Elements of a fixed size are skipped at once, rather than one task
+                        // per element, which would be many for a long list of empty elements
+                        usize::try_from(remaining)
+                            .ok()
+                            .and_then(|remaining| remaining.checked_mul(size))
+                            .ok_or(None)
+                    } else if remaining > 0 {
+                        self.tasks.push(Task::Elements {
+                            element,
+                            remaining: remaining - 1,
+                            config,
+                        });
+                        self.tasks.push(Task::Value(element, config));
+                        Ok(0)
+                    } else {
+                        Ok(0)
+                    }
+                }
+            };
+
+            match step {
+                Ok(len) if len <= rest.len() => {
+                    self.position += len;
+                    self.allocated += allocate;
+                }
+                Ok(len) => return self.retry(task, len - rest.len()),
+                Err(Some(missing)) => return self.retry(task, missing),
+                Err(None) => return Scan::Invalid,
+            }
+        }
+
+        Scan::Complete
+    }
+
+    /// Put `task` back, to be retried once `at_least` more bytes arrived.
+    fn retry(&mut self, task: Task, at_least: usize) -> Scan {
+        self.tasks.push(task);
+        Scan::NeedMore(at_least)
+    }
+}
+
+// The readers below fail with the number of missing bytes, or `None` for invalid data.
+
+/// Read an unsigned integer of type `primitive` and return it with the number of bytes read.
+fn read_uint(
+    data: &[u8],
+    primitive: Primitive,
+    config: &Config,
+) -> Result<(u64, usize), Option<usize>> {
+    if is_varint(primitive, config) {
+        return read_varint(data);
+    }
+
+    let bytes = data
+        .get(..primitive.size())
+        .ok_or_else(|| Some(primitive.size() - data.len()))?;
+    let accumulate = |value, &byte| value << 8 | byte as u64;
+
+    let value = match config.byte_order.is_big_endian() {
+        true => bytes.iter().fold(0, accumulate),
+        false => bytes.iter().rev().fold(0, accumulate),
+    };
+
+    Ok((value, bytes.len()))
+}
+
+/// Length of the varint at the start of `data`, its value is left to `deserialize` to check.
+fn varint_len(data: &[u8]) -> Result<usize, Option<usize>> {
+    match data.iter().position(|byte| byte & 0x80 == 0) {
+        Some(idx) if idx < varint::MAX_BYTES_128 => Ok(idx + 1),
+        None if data.len() < varint::MAX_BYTES_128 => Err(Some(1)),
+        _ => Err(None),
+    }
+}
+
+fn is_varint(primitive: Primitive, config: &Config) -> bool {
+    config.int_encoding == IntEncoding::Varint && primitive.is_varint()
+}
+
+fn read_varint(data: &[u8]) -> Result<(u64, usize), Option<usize>> {
+    varint::deserialize(data).map_err(|error| match error {
+        SiriusError::NotEnoughData { needed, .. } => Some(needed - data.len()),
+        _ => None,
+    })
+}
+
+fn read_length(data: &[u8], config: &Config) -> Result<(u64, usize), Option<usize>> {
+    let primitive = match config.length_prefix {
+        LengthPrefix::U8 => Primitive::U8,
+        LengthPrefix::U16 => Primitive::U16,
+        LengthPrefix::U32 => Primitive::U32,
+        LengthPrefix::U64 => Primitive::U64,
+        LengthPrefix::Varint => return read_varint(data),
+    };
+
+    read_uint(data, primitive, config)
+}
+
+fn read_tag(
+    data: &[u8],
+    tag_type: TagType,
+    config: &Config,
+) -> Result<(u64, usize), Option<usize>> {
+    let primitive = match tag_type {
+        TagType::U8 => Primitive::U8,
+        TagType::U16 => Primitive::U16,
+        TagType::U32 => Primitive::U32,
+        TagType::Varint => return read_varint(data),
+    };
+
+    read_uint(data, primitive, config)
+}
+
+#[test]
+fn test_decoder_chunks() {
+    let strings = vec!["hello".to_string(), "ਓ".to_string()];
+    let chars = ['a', '💯'];
+    let mut data = strings.serialize_buffered();
+    chars.serialize(&mut data).unwrap();
+
+    let mut decoder = Decoder::<Vec<String>>::new();
+    assert!(decoder.scanner.is_some());
+
+    let strings_len = strings.serialize_buffered().len();
+    for byte in &data[..strings_len - 1] {
+        assert!(matches!(
+            decoder.decode(&[*byte]),
+            Ok(Decoded::NeedMore { .. })
+        ));
+    }
+
+    // the last chunk holds the end of the value and the start of the next one
+    assert_eq!(
+        decoder.decode(&data[strings_len - 1..]).unwrap(),
+        Decoded::Complete(strings, 1)
+    );
+    assert_eq!(decoder.buffered(), 0);
+
+    let mut decoder = Decoder::<[char; 2]>::new();
+    assert_eq!(
+        decoder.decode(&data[strings_len..strings_len + 2]).unwrap(),
+        Decoded::NeedMore { at_least: 3 }
+    );
+    assert_eq!(
+        decoder.decode(&data[strings_len + 2..]).unwrap(),
+        Decoded::Complete(chars, 3)
+    );
+}
+
+#[test]
+fn test_decoder_at_least() {
+    let data = "hello".to_string().serialize_buffered();
+    let mut decoder = Decoder::<String>::new();
+
+    assert_eq!(
+        decoder.decode(&data[..2]).unwrap(),
+        Decoded::NeedMore { at_least: 2 }
+    );
+    assert_eq!(
+        decoder.decode(&data[2..5]).unwrap(),
+        Decoded::NeedMore { at_least: 4 }
+    );
+    assert_eq!(
+        decoder.decode(&data[5..]).unwrap(),
+        Decoded::Complete("hello".to_string(), 4)
+    );
+}
+
+#[test]
+fn test_decoder_byte_order() {
+    use crate::ByteOrder;
+
+    let config = Config::new().byte_order(ByteOrder::Little);
+    let mut data = vec![];
+    vec![0x0102u16; 3]
+        .serialize_with(&mut data, &config)
+        .unwrap();
+    assert_eq!(data[..6], [3, 0, 0, 0, 2, 1]);
+
+    let mut decoder = Decoder::<Vec<u16>>::with_config(config);
+    assert_eq!(
+        decoder.decode(&data[..4]).unwrap(),
+        Decoded::NeedMore { at_least: 2 }
+    );
+    assert_eq!(
+        decoder.decode(&data[4..]).unwrap(),
+        Decoded::Complete(vec![0x0102; 3], 6)
+    );
+}
+
+#[test]
+fn test_decoder_varint() {
+    let config = Config::new().int_encoding(IntEncoding::Varint);
+    let value = (vec![0, -1, i64::MIN], ["a".repeat(200)]);
+    let mut data = vec![];
+    value.0.serialize_with(&mut data, &config).unwrap();
+    value.1.serialize_with(&mut data, &config).unwrap();
+
+    // 1 byte of length, 1 + 1 + 10 bytes of numbers
+    let mut decoder = Decoder::<Vec<i64>>::with_config(config);
+    assert_eq!(
+        decoder.decode(&data[..3]).unwrap(),
+        Decoded::NeedMore { at_least: 1 }
+    );
+    assert_eq!(
+        decoder.decode(&data[3..]).unwrap(),
+        Decoded::Complete(value.0, 10)
+    );
+
+    // a 2-byte length prefix
+    let mut decoder = Decoder::<[String; 1]>::with_config(config);
+    assert_eq!(
+        decoder.decode(&data[13..14]).unwrap(),
+        Decoded::NeedMore { at_least: 1 }
+    );
+    assert_eq!(
+        decoder.decode(&data[14..16]).unwrap(),
+        Decoded::NeedMore { at_least: 199 }
+    );
+    assert_eq!(
+        decoder.decode(&data[16..]).unwrap(),
+        Decoded::Complete(value.1, 199)
+    );
+}
+
+#[test]
+fn test_decoder_limits() {
+    use crate::DecodeLimits;
+
+    // a string declaring 4 GiB, which the decoder would otherwise buffer waiting for
+    let config = Config::new().limits(DecodeLimits::new().max_str_len(16));
+    let mut decoder = Decoder::<Vec<String>>::with_config(config);
+    assert!(matches!(
+        decoder.decode(&[0, 0, 0, 1, 0xff, 0xff, 0xff, 0xff, b'a']),
+        Err(SiriusError::LimitExceeded {
+            limit: "max_str_len",
+            ..
+        })
+    ));
+    assert_eq!(decoder.buffered(), 0);
+
+    // strings that only allocate too much together
+    let config = Config::new().limits(DecodeLimits::new().max_alloc(16));
+    let mut decoder = Decoder::<[String; 2]>::with_config(config);
+    assert_eq!(
+        decoder.decode(&[0, 0, 0, 10, 0]).unwrap(),
+        Decoded::NeedMore { at_least: 9 }
+    );
+    assert_eq!(
+        decoder.decode(&[0; 9]).unwrap(),
+        Decoded::NeedMore { at_least: 4 }
+    );
+    assert!(matches!(
+        decoder.decode(&[0, 0, 0, 10]),
+        Err(SiriusError::LimitExceeded {
+            limit: "max_alloc",
+            ..
+        })
+    ));
+
+    // elements counted with their size in memory, not on the wire
+    let config = Config::new().limits(DecodeLimits::new().max_alloc(1024));
+    let mut decoder = Decoder::<Vec<u64>>::with_config(config);
+    assert!(matches!(
+        decoder.decode(&1_000_000u32.to_be_bytes()),
+        Err(SiriusError::LimitExceeded {
+            limit: "max_alloc",
+            ..
+        })
+    ));
+    assert_eq!(decoder.buffered(), 0);
+
+    let mut decoder = Decoder::<Vec<u64>>::with_config(config);
+    assert_eq!(
+        decoder.decode(&128u32.to_be_bytes()).unwrap(),
+        Decoded::NeedMore { at_least: 8 }
+    );
+
+    // sequences that only allocate too much together
+    let mut decoder = Decoder::<Vec<Vec<u64>>>::with_config(config);
+    let mut data = vec![0, 0, 0, 2, 0, 0, 0, 100];
+    data.extend([0; 800]);
+    assert_eq!(
+        decoder.decode(&data).unwrap(),
+        Decoded::NeedMore { at_least: 4 }
+    );
+    assert!(matches!(
+        decoder.decode(&[0, 0, 0, 100]),
+        Err(SiriusError::LimitExceeded {
+            limit: "max_alloc",
+            ..
+        })
+    ));
+
+    // billions of elements that take no bytes
+    let config = Config::new().limits(DecodeLimits::new().max_len(1024));
+    let mut decoder = Decoder::<Vec<PhantomData<u8>>>::with_config(config);
+    assert!(matches!(
+        decoder.decode(&[0xff, 0xff, 0xff, 0xff]),
+        Err(SiriusError::LimitExceeded {
+            limit: "max_len",
+            ..
+        })
+    ));
+
+    let mut decoder = Decoder::<Vec<PhantomData<u8>>>::new();
+    assert_eq!(
+        decoder.decode(&[0, 1, 0, 0]).unwrap(),
+        Decoded::Complete(vec![PhantomData; 1 << 16], 4)
+    );
+}
@@ -0,0 +1,393 @@
+//! Length-delimited framing of [`Sirius`] values on byte streams.
+//!
+//! Every frame is an optional magic byte, the length of the payload as a big-endian `u32` and
+//! the serialized value. The length lets a [`FrameReader`] skip a frame whose payload fails to
+//! decode, and the magic byte lets it find the start of the next frame after garbage, so a
+//! single corrupt frame does not poison the rest of the stream.
+
+use std::io::{self, ErrorKind, Read, Write};
+
+use crate::{Config, Sirius, SiriusError, Writer, read};
+
+/// Number of bytes of the length in a frame header.
+pub(crate) const LENGTH_BYTES: usize = std::mem::size_of::<u32>();
+
+/// Payloads are read and skipped this many bytes at a time, so that a bogus length does not
+/// allocate up front.
+#[cfg(any(feature = "futures-io", feature = "tokio-codec"))]
+pub(crate) const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Layout of the frames, which has to be the same on both ends of a stream.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameFormat {
+    magic: Option<u8>,
+    max_frame_size: Option<usize>,
+
+    /// Encoding of the payloads, the header is always big-endian.
+    config: Config<'static>,
+}
+
+impl FrameFormat {
+    /// Frames without a magic byte and without a size limit other than the `u32` length.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start every frame with `magic`.
+    pub fn magic(mut self, magic: u8) -> Self {
+        self.magic = Some(magic);
+        self
+    }
+
+    /// Refuse to write, and skip when reading, frames with a payload larger than `max` bytes.
+    pub fn max_frame_size(mut self, max: usize) -> Self {
+        self.max_frame_size = Some(max);
+        self
+    }
+
+    /// Encode the payloads as `config` says.
+    pub fn config(mut self, config: Config<'static>) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Number of bytes before the payload of a frame.
+    pub fn header_len(&self) -> usize {
+        self.magic.map_or(0, |_| 1) + LENGTH_BYTES
+    }
+
+    /// Error for a frame with a payload of `len` bytes, if it is too large.
+    pub fn check_len(&self, len: usize) -> Result<(), SiriusError> {
+        let max = self
+            .max_frame_size
+            .unwrap_or(u32::MAX as usize)
+            .min(u32::MAX as usize);
+
+        match len > max {
+            true => Err(SiriusError::FrameTooLarge { len, max }),
+            false => Ok(()),
+        }
+    }
+
+    /// Write the header of a frame with a payload of `len` bytes to the start of `buf`, which
+    /// must be [`header_len`](Self::header_len) bytes long.
+    pub fn encode_header(&self, len: usize, buf: &mut [u8]) -> Result<(), SiriusError> {
+        self.check_len(len)?;
+
+        let (magic, length) = buf.split_at_mut(self.header_len() - LENGTH_BYTES);
+        if let Some(byte) = self.magic {
+            magic[0] = byte;
+        }
+        length.copy_from_slice(&(len as u32).to_be_bytes());
+
+        Ok(())
+    }
+
+    /// Payload length of the frame starting with the header `buf`.
+    ///
+    /// A wrong magic byte is a `ParsingError`, and a payload that is too large a
+    /// [`SiriusError::FrameTooLarge`], both have to be skipped by the caller.
+    pub fn decode_header(&self, buf: &[u8]) -> Result<usize, SiriusError> {
+        let (magic, length) = buf.split_at(self.header_len() - LENGTH_BYTES);
+
+        if let Some(&byte) = magic.first() {
+            self.check_magic(byte)?;
+        }
+
+        let len = u32::from_be_bytes(length.try_into().expect("length is 4 bytes")) as usize;
+        self.check_len(len)?;
+
+        Ok(len)
+    }
+
+    pub(crate) fn check_magic(&self, byte: u8) -> Result<(), SiriusError> {
+        match self.magic {
+            Some(expected) if byte != expected => Err(SiriusError::parsing_error(
+                "frame",
+                format!("expected magic byte {expected:#04x}, found {byte:#04x}"),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Payloads of frames, encoded with the format's [`Config`].
+impl FrameFormat {
+    pub(crate) fn encode_payload<T: Sirius>(
+        &self,
+        value: &T,
+        output: &mut impl Writer,
+    ) -> Result<usize, SiriusError> {
+        value.serialize_with(output, &self.config)
+    }
+
+    /// Decode the whole `payload` of a frame, which starts after a header of `header_len`
+    /// bytes.
+    pub(crate) fn decode_payload<T: Sirius>(
+        &self,
+        payload: &[u8],
+        header_len: usize,
+    ) -> Result<T, SiriusError> {
+        let (value, bytes_read) = T::deserialize_with(payload, &self.config)
+            .map_err(|error| error.advance(header_len))?;
+
+        match bytes_read != payload.len() {
+            true => Err(SiriusError::parsing_error(
+                "frame",
+                format!(
+                    "{} bytes of the frame are not part of the value",
+                    payload.len() - bytes_read
+                ),
+            )),
+            false => Ok(value),
+        }
+    }
+}
+
+/// Writes every value as one frame to the wrapped writer.
+pub struct FrameWriter<W: Write> {
+    writer: W,
+    format: FrameFormat,
+
+    /// Reused to serialize values before their length is known.
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> FrameWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self::with_format(writer, FrameFormat::default())
+    }
+
+    pub fn with_format(writer: W, format: FrameFormat) -> Self {
+        Self {
+            writer,
+            format,
+            buffer: vec![],
+        }
+    }
+
+    /// Write `value` as one frame and return the number of bytes written, header included.
+    ///
+    /// Nothing is written if the value does not fit in a frame.
+    pub fn write<T: Sirius>(&mut self, value: &T) -> Result<usize, SiriusError> {
+        let header_len = self.format.header_len();
+
+        self.buffer.clear();
+        self.buffer.resize(header_len, 0);
+        self.format.encode_payload(value, &mut self.buffer)?;
+
+        let (header, payload) = self.buffer.split_at_mut(header_len);
+        self.format.encode_header(payload.len(), header)?;
+        self.writer.write_all(&self.buffer)?;
+
+        Ok(self.buffer.len())
+    }
+
+    pub fn flush(&mut self) -> Result<(), SiriusError> {
+        Ok(self.writer.flush()?)
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads values written by a [`FrameWriter`] from the wrapped reader.
+pub struct FrameReader<R: Read> {
+    reader: R,
+    format: FrameFormat,
+
+    /// Reused to hold the payload of the current frame.
+    buffer: Vec<u8>,
+
+    /// The last header had a wrong magic byte, so the next frame starts at the next magic byte.
+    resync: bool,
+}
+
+impl<R: Read> FrameReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_format(reader, FrameFormat::default())
+    }
+
+    pub fn with_format(reader: R, format: FrameFormat) -> Self {
+        Self {
+            reader,
+            format,
+            buffer: vec![],
+            resync: false,
+        }
+    }
+
+    /// Read the next frame as a `T`, `None` if the stream ends cleanly before the next frame.
+    ///
+    /// A frame that fails to decode is consumed whole, the error is returned and the next call
+    /// reads the frame after it. A wrong magic byte makes the next call skip bytes up to the
+    /// next magic byte. I/O errors and a stream ending inside a frame are not recoverable.
+    pub fn read<T: Sirius>(&mut self) -> Result<Option<T>, SiriusError> {
+        let mut header = [0; 1 + LENGTH_BYTES];
+        let header = &mut header[..self.format.header_len()];
+
+        if !self.read_header_start(&mut header[0])? {
+            return Ok(None);
+        }
+
+        // Checked before reading on so that the next frame is not partly consumed
+        if let Err(error) = self.format.check_magic(header[0]) {
+            self.resync = true;
+            return Err(error);
+        }
+
+        read::read_exact(&mut self.reader, &mut header[1..]).map_err(|error| error.advance(1))?;
+
+        let len = match self.format.decode_header(header) {
+            Ok(len) => len,
+            Err(error @ SiriusError::FrameTooLarge { len, .. }) => {
+                self.skip(len)?;
+                return Err(error);
+            }
+            Err(error) => return Err(error),
+        };
+
+        self.buffer.clear();
+        (&mut self.reader)
+            .take(len as u64)
+            .read_to_end(&mut self.buffer)?;
+        if self.buffer.len() < len {
+            return Err(SiriusError::not_enough_data(len, self.buffer.len()).advance(header.len()));
+        }
+
+        self.format
+            .decode_payload(&self.buffer, header.len())
+            .map(Some)
+    }
+
+    /// Read the first byte of a header into `byte`, skipping to the next magic byte first if
+    /// needed. Returns `false` if the stream ended before it.
+    fn read_header_start(&mut self, byte: &mut u8) -> Result<bool, SiriusError> {
+        loop {
+            match self.reader.read(std::slice::from_mut(byte)) {
+                Ok(0) => return Ok(false),
+                Ok(_) if self.resync && Some(*byte) != self.format.magic => {}
+                Ok(_) => {
+                    self.resync = false;
+                    return Ok(true);
+                }
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+    }
+
+    /// Discard the next `len` bytes.
+    fn skip(&mut self, len: usize) -> Result<(), SiriusError> {
+        let skipped = io::copy(&mut (&mut self.reader).take(len as u64), &mut io::sink())?;
+
+        match skipped < len as u64 {
+            true => Err(SiriusError::not_enough_data(len, skipped as usize)),
+            false => Ok(()),
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+#[test]
+fn test_frame_roundtrip() {
+    let format = FrameFormat::new().magic(0xa5);
+    let mut writer = FrameWriter::with_format(vec![], format);
+
+    assert_eq!(writer.write(&"hello".to_string()).unwrap(), 1 + 4 + 9);
+    writer.write(&7u16).unwrap();
+
+    let data = writer.into_inner();
+    assert_eq!(data[..5], [0xa5, 0, 0, 0, 9]);
+
+    let mut reader = FrameReader::with_format(&data[..], format);
+    assert_eq!(reader.read::<String>().unwrap().unwrap(), "hello");
+    assert_eq!(reader.read::<u16>().unwrap(), Some(7));
+    assert_eq!(reader.read::<u16>().unwrap(), None);
+
+    // the header stays big-endian
+    let format = FrameFormat::new().config(Config::new().byte_order(crate::ByteOrder::Little));
+    let mut writer = FrameWriter::with_format(vec![], format);
+    writer.write(&7u16).unwrap();
+
+    let data = writer.into_inner();
+    assert_eq!(data, [0, 0, 0, 2, 7, 0]);
+    assert_eq!(
+        FrameReader::with_format(&data[..], format)
+            .read::<u16>()
+            .unwrap(),
+        Some(7)
+    );
+}
+
+#[test]
+fn test_frame_skips_corrupt_frames() {
+    let format = FrameFormat::new().magic(0xa5).max_frame_size(8);
+    let mut writer = FrameWriter::with_format(vec![], format);
+
+    // a payload that is not a valid `char`
+    writer.write(&0xffu8).unwrap();
+    // a payload with bytes left over
+    writer.write(&1u32).unwrap();
+    writer.write(&'a').unwrap();
+    assert!(matches!(
+        writer.write(&"too long".to_string()),
+        Err(SiriusError::FrameTooLarge { len: 12, max: 8 })
+    ));
+
+    let mut data = writer.into_inner();
+    // garbage in front of a frame, and a frame announcing too large a payload
+    data.extend([0x00, 0x13]);
+    data.extend([0xa5, 0, 0, 0, 10]);
+    data.extend([0; 10]);
+    let mut tail = FrameWriter::with_format(vec![], format);
+    tail.write(&'b').unwrap();
+    data.extend(tail.into_inner());
+
+    let mut reader = FrameReader::with_format(&data[..], format);
+    assert!(matches!(
+        reader.read::<char>(),
+        Err(SiriusError::InvalidUtf8 { .. })
+    ));
+    assert!(matches!(
+        reader.read::<u16>(),
+        Err(SiriusError::ParsingError {
+            ty_name: "frame",
+            ..
+        })
+    ));
+    assert_eq!(reader.read::<char>().unwrap(), Some('a'));
+    assert!(matches!(
+        reader.read::<char>(),
+        Err(SiriusError::ParsingError {
+            ty_name: "frame",
+            ..
+        })
+    ));
+    assert!(matches!(
+        reader.read::<char>(),
+        Err(SiriusError::FrameTooLarge { len: 10, max: 8 })
+    ));
+    assert_eq!(reader.read::<char>().unwrap(), Some('b'));
+    assert_eq!(reader.read::<char>().unwrap(), None);
+}
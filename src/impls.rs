@@ -1,181 +1,768 @@
-use crate::*;
+use crate::{macros::default_config_methods, *};
 
-use std::{cmp::min, io::Write};
+#[cfg(feature = "alloc")]
+use crate::schema::{Schema, SchemaContext};
 
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, string::String, vec::Vec};
+#[cfg(feature = "alloc")]
+use core::cmp::min;
+use core::{cell::Cell, marker::PhantomData, mem::MaybeUninit};
+#[cfg(feature = "std")]
+use std::io::Read;
+
+#[cfg(feature = "alloc")]
 impl<T: Sirius> Sirius for Vec<T> {
-    fn serialize(&self, output: &mut impl Write) -> Result<usize, SiriusError> {
-        if self.len() >= LengthPrefix::MAX as usize {
-            return Err(SiriusError::Overflow);
-        }
+    default_config_methods!();
 
-        output.write_all(&(self.len() as LengthPrefix).to_be_bytes())?;
-        Ok(LENGTH_BYTES
+    fn serialize_with(
+        &self,
+        output: &mut impl Writer,
+        config: &Config,
+    ) -> Result<usize, SiriusError> {
+        let prefix_len = serialize_length(self.len(), output, config)?;
+        Ok(prefix_len
             + self
                 .iter()
-                .map(|item| item.serialize(output))
+                .map(|item| item.serialize_with(output, config))
                 .sum::<Result<usize, SiriusError>>()?)
     }
 
-    fn deserialize(data: &[u8]) -> Result<(Self, usize), SiriusError> {
-        let mut offset = 0;
-        let (data_len, bytes_read) = LengthPrefix::deserialize(data)?;
-        let mut deserialized: Vec<T> = Vec::with_capacity(data_len as _);
-        let ptr = deserialized.as_mut_ptr();
+    fn encoded_len_with(&self, config: &Config) -> usize {
+        length_len(self.len(), config)
+            + self
+                .iter()
+                .map(|item| item.encoded_len_with(config))
+                .sum::<usize>()
+    }
+
+    fn deserialize_with(data: &[u8], config: &Config) -> Result<(Self, usize), SiriusError> {
+        deserialize_vec(data, config, T::deserialize_with)
+    }
+
+    #[cfg(feature = "std")]
+    fn deserialize_from_with(
+        reader: &mut impl Read,
+        config: &Config,
+    ) -> Result<(Self, usize), SiriusError> {
+        let (data_len, mut offset) = deserialize_length_from(reader, config)?;
+        let allocated = Cell::new(0);
+        let config = &config.nested(&allocated)?;
+        reserve::<T>(data_len, config)?;
+
+        // The length cannot be checked against the input, which has not been read yet
+        let mut deserialized = Vec::with_capacity(preallocation::<T>(data_len, None));
 
-        offset += bytes_read;
         for i in 0..data_len {
-            let (elem, bytes_read) =
-                T::deserialize(data.get(offset..).ok_or(SiriusError::NotEnoughData)?)?;
+            let (elem, bytes_read) = T::deserialize_from_with(reader, config)
+                .map_err(|error| error.within(offset, PathSegment::Index(i)))?;
 
             offset += bytes_read;
-
-            // SAFETY: Vector is pre-allocated, so this is safe
-            unsafe { ptr.add(i as _).write(elem) };
+            deserialized.push(elem);
         }
 
-        unsafe { deserialized.set_len(data_len as _) };
-
         Ok((deserialized, offset))
     }
 }
 
+#[cfg(feature = "alloc")]
 impl<T: Sirius> Sirius for Box<[T]> {
-    fn serialize(&self, output: &mut impl Write) -> Result<usize, SiriusError> {
-        if self.len() >= LengthPrefix::MAX as usize {
-            panic!("length is greater than LengthPrefix::MAX");
-        }
+    default_config_methods!();
 
-        output.write_all(&(self.len() as LengthPrefix).to_be_bytes())?;
-        Ok(LENGTH_BYTES
+    fn serialize_with(
+        &self,
+        output: &mut impl Writer,
+        config: &Config,
+    ) -> Result<usize, SiriusError> {
+        let prefix_len = serialize_length(self.len(), output, config)?;
+        Ok(prefix_len
             + self
                 .iter()
-                .map(|i| i.serialize(output))
+                .map(|i| i.serialize_with(output, config))
                 .sum::<Result<usize, SiriusError>>()?)
     }
 
-    fn deserialize(data: &[u8]) -> Result<(Self, usize), SiriusError> {
-        let (vec, bytes_read) = Vec::<T>::deserialize(data)?;
+    fn encoded_len_with(&self, config: &Config) -> usize {
+        length_len(self.len(), config)
+            + self
+                .iter()
+                .map(|item| item.encoded_len_with(config))
+                .sum::<usize>()
+    }
+
+    fn deserialize_with(data: &[u8], config: &Config) -> Result<(Self, usize), SiriusError> {
+        let (vec, bytes_read) = Vec::<T>::deserialize_with(data, config)?;
         let boxed_slice = vec.into_boxed_slice();
         Ok((boxed_slice, bytes_read))
     }
+
+    #[cfg(feature = "std")]
+    fn deserialize_from_with(
+        reader: &mut impl Read,
+        config: &Config,
+    ) -> Result<(Self, usize), SiriusError> {
+        let (vec, bytes_read) = Vec::<T>::deserialize_from_with(reader, config)?;
+        Ok((vec.into_boxed_slice(), bytes_read))
+    }
 }
 
 impl<T: Sirius, const N: usize> Sirius for [T; N] {
-    fn serialize(&self, output: &mut impl Write) -> Result<usize, SiriusError> {
+    default_config_methods!();
+
+    fn serialize_with(
+        &self,
+        output: &mut impl Writer,
+        config: &Config,
+    ) -> Result<usize, SiriusError> {
         self.iter()
-            .map(|i| i.serialize(output))
+            .map(|i| i.serialize_with(output, config))
             .sum::<Result<usize, SiriusError>>()
     }
 
-    fn deserialize(data: &[u8]) -> Result<(Self, usize), SiriusError> {
+    fn encoded_len_with(&self, config: &Config) -> usize {
+        self.iter().map(|i| i.encoded_len_with(config)).sum()
+    }
+
+    fn deserialize_with(data: &[u8], config: &Config) -> Result<(Self, usize), SiriusError> {
+        deserialize_array(data, config, T::deserialize_with)
+    }
+
+    #[cfg(feature = "std")]
+    fn deserialize_from_with(
+        reader: &mut impl Read,
+        config: &Config,
+    ) -> Result<(Self, usize), SiriusError> {
+        let allocated = Cell::new(0);
+        let config = &config.nested(&allocated)?;
         let mut offset = 0;
-        let mut deserialized: [T; N] = unsafe { std::mem::zeroed() };
 
-        for i in deserialized.iter_mut() {
-            let (elem, bytes_read) =
-                T::deserialize(data.get(offset..).ok_or(SiriusError::NotEnoughData)?)?;
+        let deserialized = try_array(|idx| {
+            let (elem, bytes_read) = T::deserialize_from_with(reader, config)
+                .map_err(|error| error.within(offset, PathSegment::Index(idx)))?;
 
             offset += bytes_read;
-            *i = elem;
-        }
+            Ok(elem)
+        })?;
 
         Ok((deserialized, offset))
     }
 }
 
-impl Sirius for String {
-    fn serialize(&self, output: &mut impl Write) -> Result<usize, SiriusError> {
-        serialize_with_length_prefix(self.as_bytes(), output)
+/// Build an array from the elements `next` returns for every index, dropping the ones built
+/// so far when it fails.
+fn try_array<T, const N: usize>(
+    mut next: impl FnMut(usize) -> Result<T, SiriusError>,
+) -> Result<[T; N], SiriusError> {
+    let mut array = [const { MaybeUninit::<T>::uninit() }; N];
+
+    for idx in 0..N {
+        match next(idx) {
+            Ok(elem) => {
+                array[idx].write(elem);
+            }
+            Err(error) => {
+                for elem in &mut array[..idx] {
+                    // SAFETY: the elements before `idx` were written above
+                    unsafe { elem.assume_init_drop() };
+                }
+                return Err(error);
+            }
+        }
     }
 
-    fn deserialize(data: &[u8]) -> Result<(Self, usize), SiriusError> {
-        deserialize_with_length_prefix(data, |i, _| unsafe {
-            let mut s = String::with_capacity(i.len());
-            let ptr = s.as_bytes_mut().as_mut_ptr();
+    // SAFETY: every element was written above
+    Ok(array.map(|elem| unsafe { elem.assume_init() }))
+}
+
+impl<T: Sirius> Sirius for Option<T> {
+    default_config_methods!();
+
+    fn serialize_with(
+        &self,
+        output: &mut impl Writer,
+        config: &Config,
+    ) -> Result<usize, SiriusError> {
+        match self {
+            None => 0u8.serialize_with(output, config),
+            Some(value) => {
+                Ok(1u8.serialize_with(output, config)? + value.serialize_with(output, config)?)
+            }
+        }
+    }
+
+    fn encoded_len_with(&self, config: &Config) -> usize {
+        1 + self
+            .as_ref()
+            .map_or(0, |value| value.encoded_len_with(config))
+    }
+
+    fn deserialize_with(data: &[u8], config: &Config) -> Result<(Self, usize), SiriusError> {
+        deserialize_option(data, config, T::deserialize_with)
+    }
+
+    #[cfg(feature = "std")]
+    fn deserialize_from_with(
+        reader: &mut impl Read,
+        config: &Config,
+    ) -> Result<(Self, usize), SiriusError> {
+        let allocated = Cell::new(0);
+        let config = &config.nested(&allocated)?;
+
+        match u8::deserialize_from_with(reader, config)? {
+            (0, tag_len) => Ok((None, tag_len)),
+            (1, tag_len) => T::deserialize_from_with(reader, config)
+                .map(|(value, len)| (Some(value), tag_len + len))
+                .map_err(|error| error.advance(tag_len)),
+            (tag, _) => Err(invalid_option_tag(tag)),
+        }
+    }
+}
+
+// The decoding of containers is shared with the `SiriusDe` impls in `borrow`, which decode
+// their elements with `element`.
+
+/// Decode a length-prefixed sequence.
+#[cfg(feature = "alloc")]
+pub(crate) fn deserialize_vec<'de, T>(
+    data: &'de [u8],
+    config: &Config,
+    mut element: impl FnMut(&'de [u8], &Config) -> Result<(T, usize), SiriusError>,
+) -> Result<(Vec<T>, usize), SiriusError> {
+    let (data_len, mut offset) = deserialize_length(data, config)?;
+    let allocated = Cell::new(0);
+    let config = &config.nested(&allocated)?;
+    reserve::<T>(data_len, config)?;
+
+    let capacity = preallocation::<T>(data_len, Some(data.len() - offset));
+    let mut deserialized = Vec::with_capacity(capacity);
+
+    for i in 0..data_len {
+        let (elem, bytes_read) = element(
+            data.get(offset..)
+                .ok_or_else(|| SiriusError::not_enough_data(offset, data.len()))?,
+            config,
+        )
+        .map_err(|error| error.within(offset, PathSegment::Index(i)))?;
+
+        offset += bytes_read;
+        deserialized.push(elem);
+    }
+
+    Ok((deserialized, offset))
+}
+
+/// Decode `N` elements without a length.
+pub(crate) fn deserialize_array<'de, T, const N: usize>(
+    data: &'de [u8],
+    config: &Config,
+    mut element: impl FnMut(&'de [u8], &Config) -> Result<(T, usize), SiriusError>,
+) -> Result<([T; N], usize), SiriusError> {
+    let allocated = Cell::new(0);
+    let config = &config.nested(&allocated)?;
+    let mut offset = 0;
+
+    let deserialized = try_array(|idx| {
+        let (elem, bytes_read) = element(
+            data.get(offset..)
+                .ok_or_else(|| SiriusError::not_enough_data(offset, data.len()))?,
+            config,
+        )
+        .map_err(|error| error.within(offset, PathSegment::Index(idx)))?;
+
+        offset += bytes_read;
+        Ok(elem)
+    })?;
+
+    Ok((deserialized, offset))
+}
+
+/// Decode a boxed value.
+#[cfg(feature = "alloc")]
+pub(crate) fn deserialize_box<'de, T>(
+    data: &'de [u8],
+    config: &Config,
+    element: impl FnOnce(&'de [u8], &Config) -> Result<(T, usize), SiriusError>,
+) -> Result<(Box<T>, usize), SiriusError> {
+    let allocated = Cell::new(0);
+    let config = &config.nested(&allocated)?;
+    config.allocate(core::mem::size_of::<T>())?;
+
+    element(data, config).map(|(t, l)| (Box::new(t), l))
+}
+
+/// Decode an `Option`, a `u8` tag of 0 for `None` or 1 for `Some` followed by the value.
+pub(crate) fn deserialize_option<'de, T>(
+    data: &'de [u8],
+    config: &Config,
+    element: impl FnOnce(&'de [u8], &Config) -> Result<(T, usize), SiriusError>,
+) -> Result<(Option<T>, usize), SiriusError> {
+    let allocated = Cell::new(0);
+    let config = &config.nested(&allocated)?;
+
+    match u8::deserialize_with(data, config)? {
+        (0, tag_len) => Ok((None, tag_len)),
+        (1, tag_len) => element(&data[tag_len..], config)
+            .map(|(value, len)| (Some(value), tag_len + len))
+            .map_err(|error| error.advance(tag_len)),
+        (tag, _) => Err(invalid_option_tag(tag)),
+    }
+}
+
+fn invalid_option_tag(tag: u8) -> SiriusError {
+    SiriusError::parsing_error("Option", crate::__message!("invalid variant tag: {}", tag))
+}
+
+#[cfg(feature = "alloc")]
+impl Sirius for String {
+    default_config_methods!();
+
+    fn serialize_with(
+        &self,
+        output: &mut impl Writer,
+        config: &Config,
+    ) -> Result<usize, SiriusError> {
+        serialize_with_length_prefix(self.as_bytes(), output, config)
+    }
 
-            std::ptr::copy_nonoverlapping(i.as_ptr(), ptr, i.len());
-            s.as_mut_vec().set_len(i.len());
+    fn encoded_len_with(&self, config: &Config) -> usize {
+        length_len(self.len(), config) + self.len()
+    }
 
-            s
+    fn deserialize_with(data: &[u8], config: &Config) -> Result<(Self, usize), SiriusError> {
+        deserialize_with_length_prefix(data, config, |bytes| {
+            string_from_utf8(bytes.to_vec(), config)
         })
     }
+
+    #[cfg(feature = "std")]
+    fn deserialize_from_with(
+        reader: &mut impl Read,
+        config: &Config,
+    ) -> Result<(Self, usize), SiriusError> {
+        let (bytes, bytes_read) = read_with_length_prefix(reader, config)?;
+        let prefix_len = bytes_read - bytes.len();
+
+        string_from_utf8(bytes, config)
+            .map(|string| (string, bytes_read))
+            .map_err(|error| error.advance(prefix_len))
+    }
 }
 
+#[cfg(feature = "alloc")]
 impl<T: Sirius> Sirius for Box<T> {
-    fn serialize(&self, output: &mut impl Write) -> Result<usize, SiriusError> {
-        T::serialize(self, output)
+    default_config_methods!();
+
+    fn serialize_with(
+        &self,
+        output: &mut impl Writer,
+        config: &Config,
+    ) -> Result<usize, SiriusError> {
+        T::serialize_with(self, output, config)
     }
 
-    fn deserialize(data: &[u8]) -> Result<(Self, usize), SiriusError> {
-        T::deserialize(data).map(|(t, l)| (Box::new(t), l))
+    fn encoded_len_with(&self, config: &Config) -> usize {
+        T::encoded_len_with(self, config)
+    }
+
+    fn deserialize_with(data: &[u8], config: &Config) -> Result<(Self, usize), SiriusError> {
+        deserialize_box(data, config, T::deserialize_with)
+    }
+
+    #[cfg(feature = "std")]
+    fn deserialize_from_with(
+        reader: &mut impl Read,
+        config: &Config,
+    ) -> Result<(Self, usize), SiriusError> {
+        let allocated = Cell::new(0);
+        let config = &config.nested(&allocated)?;
+        config.allocate(core::mem::size_of::<T>())?;
+
+        T::deserialize_from_with(reader, config).map(|(t, l)| (Box::new(t), l))
     }
 }
 
-fn serialize_with_length_prefix(
-    slice: &[u8],
-    output: &mut impl Write,
+impl<T: ?Sized> Sirius for PhantomData<T> {
+    fn serialize(&self, _output: &mut impl Writer) -> Result<usize, SiriusError> {
+        Ok(0)
+    }
+
+    fn encoded_len_with(&self, _config: &Config) -> usize {
+        0
+    }
+
+    fn deserialize(_data: &[u8]) -> Result<(Self, usize), SiriusError> {
+        Ok((PhantomData, 0))
+    }
+
+    #[cfg(feature = "std")]
+    fn deserialize_from(_reader: &mut impl Read) -> Result<(Self, usize), SiriusError> {
+        Ok((PhantomData, 0))
+    }
+
+    #[cfg(feature = "std")]
+    fn deserialize_from_with(
+        _reader: &mut impl Read,
+        _config: &Config,
+    ) -> Result<(Self, usize), SiriusError> {
+        Ok((PhantomData, 0))
+    }
+}
+
+/// Bytes preallocated at most for a collection, which grows from there as its elements are
+/// read, so that a bogus length does not allocate up front.
+#[cfg(feature = "alloc")]
+const MAX_PREALLOCATION: usize = 64 * 1024;
+
+/// Number of elements to preallocate for a collection of `len` elements of `T`, with `remaining`
+/// bytes of input left if they are known.
+///
+/// Elements take at least a byte each, unless they take none and allocate nothing, but may be
+/// much larger in memory than on the wire, so the capacity is bounded by
+/// [`MAX_PREALLOCATION`] bytes too.
+#[cfg(feature = "alloc")]
+fn preallocation<T>(len: usize, remaining: Option<usize>) -> usize {
+    let capacity = MAX_PREALLOCATION / core::mem::size_of::<T>().max(1);
+    min(len, min(remaining.unwrap_or(len), capacity))
+}
+
+/// Check a collection of `len` elements of `T` against the [`DecodeLimits`] before it is
+/// allocated.
+#[cfg(feature = "alloc")]
+fn reserve<T>(len: usize, config: &Config) -> Result<(), SiriusError> {
+    config.limits.check_len(len)?;
+    config.allocate(len.saturating_mul(core::mem::size_of::<T>()))
+}
+
+/// Write `len` as the length prefix `config` says.
+pub(crate) fn serialize_length(
+    len: usize,
+    output: &mut impl Writer,
+    config: &Config,
 ) -> Result<usize, SiriusError> {
-    if slice.len() >= LengthPrefix::MAX as usize {
-        return Err(SiriusError::Overflow);
+    fn serialize_as<T: Sirius + TryFrom<usize>>(
+        len: usize,
+        output: &mut impl Writer,
+        config: &Config,
+    ) -> Result<usize, SiriusError> {
+        T::try_from(len)
+            .map_err(|_| SiriusError::Overflow)?
+            .serialize_with(output, config)
+    }
+
+    match config.length_prefix {
+        LengthPrefix::U8 => serialize_as::<u8>(len, output, config),
+        LengthPrefix::U16 => serialize_as::<u16>(len, output, config),
+        LengthPrefix::U32 => serialize_as::<u32>(len, output, config),
+        LengthPrefix::U64 => serialize_as::<u64>(len, output, config),
+        LengthPrefix::Varint => varint::serialize(len as u64, output),
+    }
+}
+
+/// Number of bytes [`serialize_length`] writes for `len`.
+pub(crate) fn length_len(len: usize, config: &Config) -> usize {
+    fn len_as<T: Sirius + TryFrom<usize>>(len: usize, config: &Config) -> usize {
+        T::try_from(len).map_or(0, |len| len.encoded_len_with(config))
+    }
+
+    match config.length_prefix {
+        LengthPrefix::U8 => len_as::<u8>(len, config),
+        LengthPrefix::U16 => len_as::<u16>(len, config),
+        LengthPrefix::U32 => len_as::<u32>(len, config),
+        LengthPrefix::U64 => len_as::<u64>(len, config),
+        LengthPrefix::Varint => varint::encoded_len(len as u64),
+    }
+}
+
+/// Read a length prefix written as `config` says, and return it with the number of bytes read.
+pub(crate) fn deserialize_length(
+    data: &[u8],
+    config: &Config,
+) -> Result<(usize, usize), SiriusError> {
+    fn deserialize_as<T: Sirius + Into<u64>>(
+        data: &[u8],
+        config: &Config,
+    ) -> Result<(u64, usize), SiriusError> {
+        T::deserialize_with(data, config).map(|(len, bytes_read)| (len.into(), bytes_read))
+    }
+
+    let (len, bytes_read) = match config.length_prefix {
+        LengthPrefix::U8 => deserialize_as::<u8>(data, config)?,
+        LengthPrefix::U16 => deserialize_as::<u16>(data, config)?,
+        LengthPrefix::U32 => deserialize_as::<u32>(data, config)?,
+        LengthPrefix::U64 => deserialize_as::<u64>(data, config)?,
+        LengthPrefix::Varint => varint::deserialize(data)?,
+    };
+
+    Ok((
+        usize::try_from(len).map_err(|_| SiriusError::Overflow)?,
+        bytes_read,
+    ))
+}
+
+/// Same as [`deserialize_length`], reading from `reader`.
+#[cfg(feature = "std")]
+fn deserialize_length_from(
+    reader: &mut impl Read,
+    config: &Config,
+) -> Result<(usize, usize), SiriusError> {
+    fn deserialize_as<T: Sirius + Into<u64>>(
+        reader: &mut impl Read,
+        config: &Config,
+    ) -> Result<(u64, usize), SiriusError> {
+        T::deserialize_from_with(reader, config).map(|(len, bytes_read)| (len.into(), bytes_read))
     }
 
-    output.write_all(&(slice.len() as LengthPrefix).to_be_bytes())?;
+    let (len, bytes_read) = match config.length_prefix {
+        LengthPrefix::U8 => deserialize_as::<u8>(reader, config)?,
+        LengthPrefix::U16 => deserialize_as::<u16>(reader, config)?,
+        LengthPrefix::U32 => deserialize_as::<u32>(reader, config)?,
+        LengthPrefix::U64 => deserialize_as::<u64>(reader, config)?,
+        LengthPrefix::Varint => varint::deserialize_from(reader)?,
+    };
+
+    Ok((
+        usize::try_from(len).map_err(|_| SiriusError::Overflow)?,
+        bytes_read,
+    ))
+}
+
+pub(crate) fn serialize_with_length_prefix(
+    slice: &[u8],
+    output: &mut impl Writer,
+    config: &Config,
+) -> Result<usize, SiriusError> {
+    let prefix_len = serialize_length(slice.len(), output, config)?;
     output.write_all(slice)?;
 
-    Ok(slice.len() + LENGTH_BYTES)
+    Ok(prefix_len + slice.len())
 }
 
-fn deserialize_with_length_prefix<T, F: FnOnce(&[u8], usize) -> T>(
+/// Read the length prefix of a string, checked against the [`DecodeLimits`], and pass the bytes
+/// it announces to `f`.
+#[cfg(feature = "alloc")]
+fn deserialize_with_length_prefix<T, F: FnOnce(&[u8]) -> Result<T, SiriusError>>(
     data: &[u8],
+    config: &Config,
     f: F,
 ) -> Result<(T, usize), SiriusError> {
-    let len = u32::from_be_bytes(
-        data.get(0..LENGTH_BYTES)
-            .ok_or(SiriusError::NotEnoughData)?
-            .try_into()
-            .expect("slice length is always 4 bytes because of LENGHT_BYTES constant"),
-    ) as usize;
+    let (len, prefix_len) = deserialize_length(data, config)?;
+    config.limits.check_str_len(len)?;
+    config.allocate(len)?;
+    let end = prefix_len.checked_add(len).ok_or(SiriusError::Overflow)?;
 
-    Ok((
-        f(
-            data.get(LENGTH_BYTES..len + LENGTH_BYTES)
-                .ok_or(SiriusError::NotEnoughData)?,
-            len + LENGTH_BYTES,
-        ),
-        len + LENGTH_BYTES,
-    ))
+    let bytes = data
+        .get(prefix_len..end)
+        .ok_or_else(|| SiriusError::not_enough_data(end, data.len()))?;
+
+    Ok((f(bytes).map_err(|error| error.advance(prefix_len))?, end))
+}
+
+/// Turn the bytes of a string into a `String` as the [`Utf8`] mode of `config` says, invalid
+/// UTF-8 is an [`SiriusError::InvalidUtf8`] at the offset in `bytes` of the invalid sequence.
+#[cfg(feature = "alloc")]
+fn string_from_utf8(bytes: Vec<u8>, config: &Config) -> Result<String, SiriusError> {
+    if config.is_utf8_unchecked() {
+        // SAFETY: the caller of `Config::utf8_unchecked` promised that strings are UTF-8
+        return Ok(unsafe { String::from_utf8_unchecked(bytes) });
+    }
+
+    match (String::from_utf8(bytes), config.utf8) {
+        (Ok(string), _) => Ok(string),
+        (Err(error), Utf8::Lossy) => Ok(String::from_utf8_lossy(error.as_bytes()).into_owned()),
+        (Err(error), Utf8::Strict) => {
+            Err(SiriusError::invalid_utf8(error.utf8_error().valid_up_to()))
+        }
+    }
+}
+
+/// Same as [`deserialize_with_length_prefix`] for a reader, returning the bytes and the number
+/// of bytes read.
+#[cfg(feature = "std")]
+fn read_with_length_prefix(
+    reader: &mut impl Read,
+    config: &Config,
+) -> Result<(Vec<u8>, usize), SiriusError> {
+    let (len, prefix_len) = deserialize_length_from(reader, config)?;
+    config.limits.check_str_len(len)?;
+    config.allocate(len)?;
+
+    // Read through `take` so that a bogus length does not allocate up front
+    let mut bytes = vec![];
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+
+    if bytes.len() < len {
+        return Err(SiriusError::not_enough_data(
+            prefix_len + len,
+            prefix_len + bytes.len(),
+        ));
+    }
+
+    Ok((bytes, prefix_len + len))
 }
 
 impl Sirius for char {
-    fn serialize(&self, output: &mut impl Write) -> Result<usize, SiriusError> {
+    fn serialize(&self, output: &mut impl Writer) -> Result<usize, SiriusError> {
         let mut buf = [0u8; 4];
         let encoded = self.encode_utf8(&mut buf);
         output.write_all(encoded.as_bytes())?;
         Ok(encoded.len())
     }
 
+    fn encoded_len_with(&self, _config: &Config) -> usize {
+        self.len_utf8()
+    }
+
     fn deserialize(data: &[u8]) -> Result<(Self, usize), SiriusError> {
-        // Try to decode a char from the first 1 to 4 bytes
-        for len in 1..=min(4, data.len()) {
-            if let Ok(s) = std::str::from_utf8(&data[..len]) {
-                if let Some(ch) = s.chars().next() {
-                    if ch.len_utf8() == len {
-                        return Ok((ch, len));
-                    }
-                }
-            }
+        let needed = data.first().map_or(1, |&byte| utf8_len(byte));
+
+        if data.len() < needed {
+            return Err(SiriusError::not_enough_data(needed, data.len()));
+        }
+
+        match core::str::from_utf8(&data[..needed]) {
+            Ok(s) => Ok((s.chars().next().expect("`needed` is at least 1"), needed)),
+            Err(_) => Err(SiriusError::invalid_utf8(0)),
         }
-        Err(SiriusError::ParsingError {
-            ty_name: "char",
-            error: "invalid UTF-8 sequence for char".to_string(),
-        })
+    }
+
+    #[cfg(feature = "std")]
+    fn deserialize_from(reader: &mut impl Read) -> Result<(Self, usize), SiriusError> {
+        let mut buf = [0u8; 4];
+        read::read_exact(reader, &mut buf[..1])?;
+
+        let len = utf8_len(buf[0]);
+        read::read_exact(reader, &mut buf[1..len]).map_err(|error| error.advance(1))?;
+
+        Self::deserialize(&buf[..len])
+    }
+
+    #[cfg(feature = "std")]
+    fn deserialize_from_with(
+        reader: &mut impl Read,
+        _config: &Config,
+    ) -> Result<(Self, usize), SiriusError> {
+        Self::deserialize_from(reader)
+    }
+}
+
+/// Length of the UTF-8 encoding starting with `byte`, continuation and invalid leading bytes
+/// count as one byte and are rejected when decoding.
+pub(crate) fn utf8_len(byte: u8) -> usize {
+    match byte {
+        _ if byte & 0xe0 == 0xc0 => 2,
+        _ if byte & 0xf0 == 0xe0 => 3,
+        _ if byte & 0xf8 == 0xf0 => 4,
+        _ => 1,
     }
 }
 
 impl_sirius_for_numbers! {
-    u8, u16, u32, u64, u128,
-    i8, i16, i32, i64, i128,
-    f32, f64, usize, isize
+    u8 => U8, u16 => U16 as u128, u32 => U32 as u128, u64 => U64 as u128, u128 => U128 as u128,
+    i8 => I8, i16 => I16 as i128, i32 => I32 as i128, i64 => I64 as i128, i128 => I128 as i128,
+    f32 => F32, f64 => F64, usize => Usize as u128, isize => Isize as i128,
+}
+
+#[cfg(feature = "alloc")]
+impl<T: SiriusSchema> SiriusSchema for Vec<T> {
+    fn describe(context: &mut SchemaContext) -> Schema {
+        Schema::Sequence(Box::new(T::describe(context)))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: SiriusSchema> SiriusSchema for Box<[T]> {
+    fn describe(context: &mut SchemaContext) -> Schema {
+        Vec::<T>::describe(context)
+    }
+}
+
+impl<T: SiriusSchema, const N: usize> SiriusSchema for [T; N] {
+    #[cfg(feature = "alloc")]
+    fn describe(context: &mut SchemaContext) -> Schema {
+        Schema::Array {
+            len: N,
+            element: Box::new(T::describe(context)),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl SiriusSchema for String {
+    fn describe(_context: &mut SchemaContext) -> Schema {
+        Schema::String
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: SiriusSchema> SiriusSchema for Box<T> {
+    fn describe(context: &mut SchemaContext) -> Schema {
+        T::describe(context)
+    }
+}
+
+impl<T: ?Sized> SiriusSchema for PhantomData<T> {
+    #[cfg(feature = "alloc")]
+    fn describe(_context: &mut SchemaContext) -> Schema {
+        Schema::Unit
+    }
+}
+
+impl SiriusSchema for char {
+    #[cfg(feature = "alloc")]
+    fn describe(_context: &mut SchemaContext) -> Schema {
+        Schema::Char
+    }
+}
+
+/// An enum of the variants `None` with tag 0 and `Some` with tag 1.
+impl<T: SiriusSchema> SiriusSchema for Option<T> {
+    #[cfg(feature = "alloc")]
+    fn describe(context: &mut SchemaContext) -> Schema {
+        use crate::schema::{EnumSchema, FieldSchema, TagType, VariantSchema};
+
+        Schema::Enum(EnumSchema {
+            name: "Option",
+            tag_type: TagType::U8,
+            auto_tag_type: false,
+            variants: alloc::vec![
+                VariantSchema {
+                    name: "None",
+                    tag: 0,
+                    fields: Vec::new(),
+                },
+                VariantSchema {
+                    name: "Some",
+                    tag: 1,
+                    fields: alloc::vec![FieldSchema {
+                        name: "0",
+                        since: None,
+                        until: None,
+                        varint: false,
+                        length_prefix: None,
+                        schema: T::describe(context),
+                    }],
+                },
+            ],
+        })
+    }
+}
+
+#[test]
+fn test_schema_sirius() {
+    assert_eq!(u16::schema(), Schema::Primitive(schema::Primitive::U16));
+    assert_eq!(
+        Vec::<[char; 2]>::schema(),
+        Schema::Sequence(Box::new(Schema::Array {
+            len: 2,
+            element: Box::new(Schema::Char),
+        }))
+    );
+    assert_eq!(Box::<String>::schema(), String::schema());
+    assert_eq!(Box::<[u8]>::schema(), Vec::<u8>::schema());
+    assert_eq!(
+        PhantomData::<str>::describe(&mut Default::default()),
+        Schema::Unit
+    );
 }
 
 #[test]
@@ -200,6 +787,21 @@ fn test_array_sirius() {
 
     assert!(data.iter().zip(n.iter()).all(|(&a, &b)| a == b));
     assert_eq!(bytes_read, v.len());
+
+    // elements for which zeroed memory is not a valid value
+    let boxes = [Box::new(1u32), Box::new(2)];
+    assert_eq!(
+        <[Box<u32>; 2]>::deserialize(&boxes.serialize_buffered()).unwrap(),
+        (boxes, 8)
+    );
+
+    // the strings read before the error are dropped
+    let mut v = ["a".to_string(), "b".to_string()].serialize_buffered();
+    v.extend([0, 0, 0, 1, 0xff]);
+    assert!(matches!(
+        <[String; 3]>::deserialize(&v),
+        Err(SiriusError::InvalidUtf8 { offset: 14, .. })
+    ));
 }
 
 #[test]
@@ -209,11 +811,12 @@ fn test_char_sirius_check() {
 
     let data = 0xf0450000_u32.to_be_bytes();
     assert!(matches!(
-        dbg!(char::deserialize(&data)),
-        Err(SiriusError::ParsingError {
-            ty_name: "char",
-            ..
-        })
+        char::deserialize(&data),
+        Err(SiriusError::InvalidUtf8 { offset: 0, .. })
+    ));
+    assert!(matches!(
+        char::deserialize(&[0x80]),
+        Err(SiriusError::InvalidUtf8 { offset: 0, .. })
     ));
 }
 
@@ -252,3 +855,370 @@ fn test_boxed_slice_sirius() {
     assert_eq!(deserialized, original);
     assert_eq!(bytes_read, serialized.len());
 }
+
+#[test]
+fn test_preallocation() {
+    // a million elements of a byte each on the wire, and 4 KiB each in memory
+    assert_eq!(
+        preallocation::<Option<[u8; 4096]>>(1_000_000, Some(1 << 20)),
+        MAX_PREALLOCATION / 4097
+    );
+    assert_eq!(preallocation::<u8>(1_000_000, Some(10)), 10);
+    assert_eq!(preallocation::<u8>(10, None), 10);
+    assert_eq!(preallocation::<u64>(1_000_000, None), MAX_PREALLOCATION / 8);
+    assert_eq!(preallocation::<()>(1_000_000, Some(0)), 0);
+}
+
+#[test]
+fn test_option_sirius() {
+    let values = vec![Some("a".to_string()), None];
+    let serialized = values.serialize_buffered();
+    assert_eq!(serialized, [0, 0, 0, 2, 1, 0, 0, 0, 1, b'a', 0]);
+    assert_eq!(values.encoded_len(), serialized.len());
+    assert_eq!(
+        Vec::<Option<String>>::deserialize(&serialized).unwrap(),
+        (values.clone(), 11)
+    );
+    assert_eq!(
+        Vec::<Option<String>>::deserialize_from(&mut &serialized[..]).unwrap(),
+        (values, 11)
+    );
+
+    assert!(matches!(
+        Option::<u8>::deserialize(&[2]),
+        Err(SiriusError::ParsingError {
+            ty_name: "Option",
+            ..
+        })
+    ));
+    assert!(matches!(
+        Option::<u16>::deserialize(&[1, 0]),
+        Err(SiriusError::NotEnoughData { offset: 1, .. })
+    ));
+}
+
+#[test]
+fn test_deserialize_from_sirius() {
+    let strings = vec!["a".to_string(), "ਓb".to_string()];
+    let mut data = strings.serialize_buffered();
+    let chars = ['x', '💯'];
+    chars.serialize(&mut data).unwrap();
+    let boxed: Box<[u16]> = Box::new([1, 2, 3]);
+    boxed.serialize(&mut data).unwrap();
+    data.push(0xff);
+
+    let mut reader = &data[..];
+    let (deserialized, strings_len) = Vec::<String>::deserialize_from(&mut reader).unwrap();
+    assert_eq!(deserialized, strings);
+    assert_eq!(strings_len, strings.serialize_buffered().len());
+    assert_eq!(
+        <[char; 2]>::deserialize_from(&mut reader).unwrap(),
+        (chars, 5)
+    );
+    assert_eq!(
+        Box::<[u16]>::deserialize_from(&mut reader).unwrap(),
+        (boxed, 10)
+    );
+
+    // only the bytes of the values are consumed
+    assert_eq!(reader, [0xff]);
+
+    let mut reader = &data[..strings_len - 1];
+    match Vec::<String>::deserialize_from(&mut reader) {
+        Err(SiriusError::NotEnoughData {
+            offset,
+            needed,
+            available,
+            path,
+        }) => {
+            assert_eq!((offset, needed, available), (9, 8, 7));
+            assert_eq!(path.to_string(), "[1]");
+        }
+        other => panic!("unexpected result: {other:?}"),
+    }
+}
+
+#[test]
+fn test_deserialize_from_io_error() {
+    struct Failing;
+
+    impl Read for Failing {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("connection reset"))
+        }
+    }
+
+    assert!(matches!(
+        String::deserialize_from(&mut Failing),
+        Err(SiriusError::IoError(_))
+    ));
+}
+
+#[test]
+fn test_length_prefix_sirius() {
+    let values = vec![1u16; 300];
+    let boxed: Box<[u16]> = values.clone().into();
+
+    for (length_prefix, prefix) in [
+        (LengthPrefix::U16, &[1, 44][..]),
+        (LengthPrefix::U64, &[0, 0, 0, 0, 0, 0, 1, 44][..]),
+        (LengthPrefix::Varint, &[0xac, 0x02][..]),
+    ] {
+        let config = Config::new().length_prefix(length_prefix);
+        let mut data = vec![];
+        let bytes_written = boxed.serialize_with(&mut data, &config).unwrap();
+
+        assert_eq!(bytes_written, data.len());
+        assert!(data.starts_with(prefix));
+        assert_eq!(
+            Vec::<u16>::deserialize_with(&data, &config).unwrap(),
+            (values.clone(), data.len())
+        );
+        assert_eq!(
+            Vec::<u16>::deserialize_from_with(&mut &data[..], &config).unwrap(),
+            (values.clone(), data.len())
+        );
+    }
+
+    let config = Config::new().length_prefix(LengthPrefix::U8);
+    assert!(matches!(
+        values.serialize_with(&mut vec![], &config),
+        Err(SiriusError::Overflow)
+    ));
+    assert!(matches!(
+        boxed.serialize_with(&mut vec![], &config),
+        Err(SiriusError::Overflow)
+    ));
+    assert!(matches!(
+        "a".repeat(256).serialize_with(&mut vec![], &config),
+        Err(SiriusError::Overflow)
+    ));
+
+    // a length that no buffer can hold
+    let config = Config::new().length_prefix(LengthPrefix::U64);
+    assert!(matches!(
+        String::deserialize_with(&[0xff; 9], &config),
+        Err(SiriusError::Overflow)
+    ));
+}
+
+#[test]
+fn test_decode_limits() {
+    let limit = |limits: DecodeLimits| Config::new().limits(limits);
+    fn exceeded<T>(result: Result<T, SiriusError>) -> &'static str {
+        match result {
+            Err(SiriusError::LimitExceeded { limit, .. }) => limit,
+            Err(error) => panic!("unexpected error: {error}"),
+            Ok(_) => panic!("the limit was not enforced"),
+        }
+    }
+
+    // a length of 2^32 - 1 without the elements
+    let bogus = [0xff, 0xff, 0xff, 0xff];
+    assert!(matches!(
+        Vec::<u64>::deserialize(&bogus),
+        Err(SiriusError::NotEnoughData { .. })
+    ));
+    let config = limit(DecodeLimits::new().max_alloc(1 << 20));
+    assert_eq!(
+        exceeded(Vec::<u64>::deserialize_with(&bogus, &config)),
+        "max_alloc"
+    );
+    assert_eq!(
+        exceeded(Vec::<u64>::deserialize_from_with(&mut &bogus[..], &config)),
+        "max_alloc"
+    );
+
+    let data = vec![1u8; 10].serialize_buffered();
+    let config = limit(DecodeLimits::new().max_len(9));
+    assert_eq!(
+        exceeded(Vec::<u8>::deserialize_with(&data, &config)),
+        "max_len"
+    );
+    assert_eq!(
+        exceeded(String::deserialize_with(
+            &data,
+            &config.limits(DecodeLimits::new().max_str_len(9))
+        )),
+        "max_str_len"
+    );
+
+    let boxed = Box::new(Box::new(1u8)).serialize_buffered();
+    let config = limit(DecodeLimits::new().max_depth(1));
+    assert_eq!(
+        exceeded(Box::<Box<u8>>::deserialize_with(&boxed, &config)),
+        "max_depth"
+    );
+    let config = limit(DecodeLimits::new().max_depth(2));
+    assert!(Box::<Box<u8>>::deserialize_with(&boxed, &config).is_ok());
+
+    // the vector and the strings in it count towards the same total
+    let strings = vec!["0123456789".to_string(); 3];
+    let data = strings.serialize_buffered();
+    let total = 3 * core::mem::size_of::<String>() + 30;
+    let config = limit(DecodeLimits::new().max_alloc(total));
+    assert_eq!(
+        Vec::<String>::deserialize_with(&data, &config).unwrap(),
+        (strings.clone(), data.len())
+    );
+    assert_eq!(
+        Vec::<String>::deserialize_from_with(&mut &data[..], &config).unwrap(),
+        (strings, data.len())
+    );
+    let config = limit(DecodeLimits::new().max_alloc(total - 1));
+    assert_eq!(
+        exceeded(Vec::<String>::deserialize_with(&data, &config)),
+        "max_alloc"
+    );
+    assert_eq!(
+        exceeded(Vec::<String>::deserialize_from_with(
+            &mut &data[..],
+            &config
+        )),
+        "max_alloc"
+    );
+}
+
+#[test]
+fn test_encoded_len_sirius() {
+    fn check<T: Sirius>(value: T) {
+        for config in [
+            Config::new(),
+            Config::new().int_encoding(IntEncoding::Varint),
+            Config::new().length_prefix(LengthPrefix::U8),
+            Config::new().length_prefix(LengthPrefix::Varint),
+        ] {
+            let mut data = vec![];
+            value.serialize_with(&mut data, &config).unwrap();
+            assert_eq!(value.encoded_len_with(&config), data.len());
+        }
+    }
+
+    check(vec![1u32, 300, u32::MAX]);
+    check(vec!["a".to_string(), "b".repeat(200)]);
+    check([-1i64, 0, i64::MIN]);
+    check(Box::new(['💯', 'a']));
+    check(vec![1u16; 100].into_boxed_slice());
+    check(PhantomData::<u8>);
+
+    let value = vec![1u32, 2];
+    let mut buf = [0; 12];
+    assert_eq!(value.serialize_into_slice(&mut buf).unwrap(), 12);
+    assert_eq!(buf[..], value.serialize_buffered());
+
+    let mut buf = [0xff; 11];
+    assert!(matches!(
+        value.serialize_into_slice(&mut buf),
+        Err(SiriusError::BufferTooSmall {
+            needed: 12,
+            available: 11,
+        })
+    ));
+    assert_eq!(buf, [0xff; 11]);
+}
+
+/// Writes a byte and then fails, like a value whose length prefix overflows midway.
+#[cfg(test)]
+struct Failing;
+
+#[cfg(test)]
+impl SiriusSchema for Failing {}
+
+#[cfg(test)]
+impl Sirius for Failing {
+    fn serialize(&self, output: &mut impl Writer) -> Result<usize, SiriusError> {
+        output.write_all(&[1])?;
+        Err(SiriusError::Overflow)
+    }
+
+    fn deserialize(_data: &[u8]) -> Result<(Self, usize), SiriusError> {
+        Ok((Failing, 0))
+    }
+}
+
+/// Counts how many times it is serialized.
+#[cfg(test)]
+#[derive(Default)]
+struct Counting(Cell<usize>);
+
+#[cfg(test)]
+impl SiriusSchema for Counting {}
+
+#[cfg(test)]
+impl Sirius for Counting {
+    fn serialize(&self, output: &mut impl Writer) -> Result<usize, SiriusError> {
+        self.0.set(self.0.get() + 1);
+        output.write_all(&[1])?;
+        Ok(1)
+    }
+
+    fn deserialize(_data: &[u8]) -> Result<(Self, usize), SiriusError> {
+        Ok((Counting::default(), 0))
+    }
+}
+
+#[test]
+fn test_try_to_vec() {
+    let strings = vec!["a".to_string(), "bc".to_string()];
+    let data = strings.try_to_vec().unwrap();
+    assert_eq!(
+        Vec::<String>::deserialize(&data).unwrap(),
+        (strings, data.len())
+    );
+
+    assert!(matches!(Failing.try_to_vec(), Err(SiriusError::Overflow)));
+
+    // the value is serialized once, not measured first
+    let counting = Counting::default();
+    counting.try_to_vec().unwrap();
+    assert_eq!(counting.0.get(), 1);
+
+    let mut output = vec![9];
+    assert_eq!(7u16.serialize_append(&mut output).unwrap(), 2);
+    assert_eq!(output, [9, 0, 7]);
+    assert!(matches!(
+        Failing.serialize_append(&mut output),
+        Err(SiriusError::Overflow)
+    ));
+    assert_eq!(output, [9, 0, 7]);
+}
+
+#[test]
+#[should_panic(expected = "failed to serialize")]
+fn test_serialize_buffered_panics() {
+    Failing.serialize_buffered();
+}
+
+#[test]
+fn test_string_utf8() {
+    let data = [0, 0, 0, 4, b'a', 0xff, b'b', b'c'];
+
+    assert!(matches!(
+        String::deserialize(&data),
+        Err(SiriusError::InvalidUtf8 { offset: 5, .. })
+    ));
+    assert!(matches!(
+        String::deserialize_from(&mut &data[..]),
+        Err(SiriusError::InvalidUtf8 { offset: 5, .. })
+    ));
+    assert!(matches!(
+        Vec::<String>::deserialize(&[&[0, 0, 0, 1][..], &data].concat()),
+        Err(SiriusError::InvalidUtf8 { offset: 9, ref path }) if path.to_string() == "[0]"
+    ));
+
+    let config = Config::new().utf8(Utf8::Lossy);
+    let lossy = ("a\u{FFFD}bc".to_string(), data.len());
+    assert_eq!(String::deserialize_with(&data, &config).unwrap(), lossy);
+    assert_eq!(
+        String::deserialize_from_with(&mut &data[..], &config).unwrap(),
+        lossy
+    );
+
+    let valid = "ok".to_string().serialize_buffered();
+    // SAFETY: `valid` is UTF-8
+    let config = unsafe { Config::new().utf8_unchecked() };
+    assert_eq!(
+        String::deserialize_with(&valid, &config).unwrap(),
+        ("ok".to_string(), valid.len())
+    );
+}
@@ -1,51 +1,364 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+use core::fmt;
+
 use thiserror::Error;
 
+#[cfg(feature = "futures-io")]
+pub mod async_io;
+pub mod borrow;
+#[cfg(feature = "tokio-codec")]
+pub mod codec;
+pub mod config;
+#[cfg(feature = "alloc")]
+pub mod decoder;
+#[cfg(feature = "std")]
+pub mod frame;
 mod impls;
 mod macros;
+pub mod path;
+#[cfg(feature = "std")]
+pub mod read;
+pub mod schema;
+pub mod varint;
+pub mod writer;
 
+pub use borrow::{SiriusDe, SiriusEncode};
+pub use config::{ByteOrder, Config, DecodeLimits, IntEncoding, LengthPrefix, Utf8};
+pub use path::{FieldPath, PathSegment};
+pub use schema::SiriusSchema;
 pub use sirius_macros::Sirius;
+#[cfg(feature = "std")]
+pub use writer::IoWriter;
+pub use writer::{ByteCounter, Writer};
 
-/// The type that will be used to store the length of the slice.
-pub type LengthPrefix = u32;
-
-/// Number of bytes used to store the length of the slice.
-const LENGTH_BYTES: usize = std::mem::size_of::<LengthPrefix>();
+#[doc(hidden)]
+pub mod __private {
+    #[cfg(feature = "alloc")]
+    pub use alloc::{format, vec};
+}
 
 /// A trait for **data structures** that can be serialized or deserialized into binary.
 ///
 /// To make the process faster, it tries to avoid allocations as much as possible,
-/// this is why [serialize] function takes a `&mut impl Write`. Alternatively, you can use
-/// the [serialize_buffered] function.
-pub trait Sirius {
+/// this is why [serialize] function takes a `&mut impl Writer`. Alternatively, you can use
+/// the [try_to_vec] and [serialize_append] functions.
+///
+/// [serialize]: Sirius::serialize
+/// [try_to_vec]: Sirius::try_to_vec
+/// [serialize_append]: Sirius::serialize_append
+///
+/// Every implementor also describes its wire layout through [`SiriusSchema`].
+pub trait Sirius: SiriusSchema {
     /// Write the serialized data to output and return the number of bytes written
-    fn serialize(&self, output: &mut impl std::io::Write) -> Result<usize, SiriusError>;
+    fn serialize(&self, output: &mut impl Writer) -> Result<usize, SiriusError>;
 
     /// Deserialize the data and return the number of bytes read
     fn deserialize(data: &[u8]) -> Result<(Self, usize), SiriusError>
     where
         Self: Sized;
 
-    fn serialize_buffered(&self) -> Vec<u8> {
-        let mut data = vec![];
-        _ = Sirius::serialize(self, &mut data);
-        data
+    /// Deserialize a value from `reader`, reading only the bytes it takes, and return the
+    /// number of bytes read
+    ///
+    /// Running out of input is reported as [`SiriusError::NotEnoughData`], any other failure of
+    /// the reader as [`SiriusError::IoError`]. The default implementation goes through
+    /// [`read::read_with`], which re-runs [`deserialize`](Sirius::deserialize) as bytes arrive.
+    #[cfg(feature = "std")]
+    fn deserialize_from(reader: &mut impl std::io::Read) -> Result<(Self, usize), SiriusError>
+    where
+        Self: Sized,
+    {
+        read::read_with(reader, Self::deserialize)
+    }
+
+    /// Same as [`serialize`](Sirius::serialize), encoding the value as `config` says
+    ///
+    /// The default implementation ignores `config`, built-in and derived impls override it.
+    fn serialize_with(
+        &self,
+        output: &mut impl Writer,
+        config: &Config,
+    ) -> Result<usize, SiriusError> {
+        _ = config;
+        self.serialize(output)
+    }
+
+    /// Same as [`deserialize`](Sirius::deserialize), for data encoded as `config` says
+    fn deserialize_with(data: &[u8], config: &Config) -> Result<(Self, usize), SiriusError>
+    where
+        Self: Sized,
+    {
+        _ = config;
+        Self::deserialize(data)
+    }
+
+    /// Same as [`deserialize_from`](Sirius::deserialize_from), for data encoded as `config` says
+    #[cfg(feature = "std")]
+    fn deserialize_from_with(
+        reader: &mut impl std::io::Read,
+        config: &Config,
+    ) -> Result<(Self, usize), SiriusError>
+    where
+        Self: Sized,
+    {
+        read::read_with(reader, |data| Self::deserialize_with(data, config))
+    }
+
+    /// Number of bytes [`serialize`](Sirius::serialize) writes for the value
+    fn encoded_len(&self) -> usize {
+        self.encoded_len_with(&Config::default())
+    }
+
+    /// Same as [`encoded_len`](Sirius::encoded_len), for the value encoded as `config` says
+    ///
+    /// The default implementation serializes the value into a [`ByteCounter`], built-in and
+    /// derived impls compute the length without serializing. The length of a value that fails
+    /// to serialize is unspecified.
+    fn encoded_len_with(&self, config: &Config) -> usize {
+        let mut counter = ByteCounter::default();
+        _ = self.serialize_with(&mut counter, config);
+        counter.0
+    }
+
+    /// Write the serialized data to the start of `buf` and return the number of bytes written
+    ///
+    /// Fails with [`SiriusError::BufferTooSmall`] without writing anything if the value takes
+    /// more than `buf.len()` bytes, as told by [`encoded_len`](Sirius::encoded_len).
+    fn serialize_into_slice(&self, buf: &mut [u8]) -> Result<usize, SiriusError> {
+        let needed = self.encoded_len();
+        if needed > buf.len() {
+            return Err(SiriusError::BufferTooSmall {
+                needed,
+                available: buf.len(),
+            });
+        }
+
+        let mut output = buf;
+        self.serialize(&mut output)
+    }
+
+    /// Serialize the value into a new `Vec`
+    ///
+    /// The value is traversed once, the `Vec` grows as it is written. Use
+    /// [`encoded_len`](Sirius::encoded_len) and [`serialize_append`](Sirius::serialize_append)
+    /// to allocate the exact size up front instead.
+    #[cfg(feature = "alloc")]
+    fn try_to_vec(&self) -> Result<alloc::vec::Vec<u8>, SiriusError> {
+        let mut data = alloc::vec::Vec::new();
+        self.serialize(&mut data)?;
+        Ok(data)
+    }
+
+    /// Write the serialized data to the end of `output`, reusing its allocation, and return the
+    /// number of bytes written
+    ///
+    /// If serializing fails, `output` is truncated back to its length before the call, so that
+    /// no partially written value is left in it.
+    #[cfg(feature = "alloc")]
+    fn serialize_append(&self, output: &mut alloc::vec::Vec<u8>) -> Result<usize, SiriusError> {
+        let len = output.len();
+        self.serialize(output).inspect_err(|_| output.truncate(len))
+    }
+
+    /// Same as [`try_to_vec`](Sirius::try_to_vec), for values that cannot fail to serialize
+    ///
+    /// # Panics
+    ///
+    /// If serializing fails, e.g. with [`SiriusError::Overflow`] for a string too long for its
+    /// length prefix. Versions up to 0.1.1 dropped the error and returned the partial data.
+    #[cfg(feature = "alloc")]
+    fn serialize_buffered(&self) -> alloc::vec::Vec<u8> {
+        match self.try_to_vec() {
+            Ok(data) => data,
+            Err(error) => panic!("failed to serialize: {error}"),
+        }
     }
 }
 
+/// Errors returned while serializing or deserializing.
+///
+/// Decoding errors of nested values carry the absolute byte `offset` at which the failing value
+/// starts and the [`FieldPath`] leading to it, both are filled in as the error propagates up.
 #[derive(Debug, Error)]
 pub enum SiriusError {
-    #[error("ran out of data bytes while parsing, cannot deserialize the remaining fields")]
-    NotEnoughData,
+    #[error(
+        "ran out of data at byte {offset}{}: needed {needed} bytes but only {available} were available",
+        location(.path)
+    )]
+    NotEnoughData {
+        offset: usize,
+
+        /// Bytes the failing value needed, counted from `offset`.
+        needed: usize,
+
+        /// Bytes that were left, counted from `offset`.
+        available: usize,
 
+        path: FieldPath,
+    },
+
+    /// A length does not fit in the configured [`LengthPrefix`], or a length read does not fit
+    /// in a `usize`.
     #[error("alleged slice length exceeded the maximum allowed length")]
     Overflow,
 
-    #[error("failed to parse data as `{ty_name}`: {error}")]
+    #[error("failed to parse data as `{ty_name}` at byte {offset}{}: {error}", location(.path))]
     ParsingError {
         ty_name: &'static str,
-        error: String,
+        error: ErrorMessage,
+        offset: usize,
+        path: FieldPath,
     },
 
+    /// A string or `char` that is not valid UTF-8, `offset` is the byte at which the invalid
+    /// sequence starts.
+    #[error("invalid UTF-8 at byte {offset}{}", location(.path))]
+    InvalidUtf8 { offset: usize, path: FieldPath },
+
+    /// A value rejected by its `#[sirius(validate = "...")]` function, `offset` is the byte at
+    /// which the value starts.
+    #[error("invalid value at byte {offset}{}: {error}", location(.path))]
+    ValidationError {
+        offset: usize,
+        path: FieldPath,
+        error: ErrorMessage,
+    },
+
+    #[error("frame of {len} bytes exceeds the maximum frame size of {max} bytes")]
+    FrameTooLarge { len: usize, max: usize },
+
+    #[error("output buffer is full: writing {needed} bytes but only {available} are left")]
+    BufferTooSmall { needed: usize, available: usize },
+
+    /// Decoding went past one of the [`DecodeLimits`] of the [`Config`].
+    #[error("decoding exceeded `{limit}`: {value} is more than the limit of {max}")]
+    LimitExceeded {
+        /// Name of the exceeded [`DecodeLimits`] field.
+        limit: &'static str,
+        value: usize,
+        max: usize,
+    },
+
+    #[cfg(feature = "std")]
     #[error("I/O error: {0}")]
     IoError(#[from] std::io::Error),
 }
+
+impl SiriusError {
+    /// `NotEnoughData` for a value that needed `needed` bytes when only `available` were left.
+    pub fn not_enough_data(needed: usize, available: usize) -> Self {
+        Self::NotEnoughData {
+            offset: 0,
+            needed,
+            available,
+            path: FieldPath::default(),
+        }
+    }
+
+    /// `LimitExceeded` for a `value` of the limit `limit` that is more than `max`.
+    pub fn limit_exceeded(limit: &'static str, value: usize, max: usize) -> Self {
+        Self::LimitExceeded { limit, value, max }
+    }
+
+    /// `InvalidUtf8` for an invalid sequence starting `offset` bytes into the value.
+    pub fn invalid_utf8(offset: usize) -> Self {
+        Self::InvalidUtf8 {
+            offset,
+            path: FieldPath::default(),
+        }
+    }
+
+    /// `ValidationError` for a value a validation function rejected with `error`.
+    pub fn validation_error(error: impl Into<ErrorMessage>) -> Self {
+        Self::ValidationError {
+            offset: 0,
+            path: FieldPath::default(),
+            error: error.into(),
+        }
+    }
+
+    /// `ParsingError` for data that is not a valid `ty_name`.
+    pub fn parsing_error(ty_name: &'static str, error: impl Into<ErrorMessage>) -> Self {
+        Self::ParsingError {
+            ty_name,
+            error: error.into(),
+            offset: 0,
+            path: FieldPath::default(),
+        }
+    }
+
+    /// Shifts the error's offset for a failing value that starts `by` bytes into the value
+    /// being decoded.
+    pub fn advance(mut self, by: usize) -> Self {
+        if let Self::NotEnoughData { offset, .. }
+        | Self::ParsingError { offset, .. }
+        | Self::InvalidUtf8 { offset, .. }
+        | Self::ValidationError { offset, .. } = &mut self
+        {
+            *offset += by;
+        }
+
+        self
+    }
+
+    /// [`advance`](Self::advance)s the error and records the field, variant or element the
+    /// failing value is part of.
+    pub fn within(mut self, offset: usize, segment: PathSegment) -> Self {
+        if let Self::NotEnoughData { path, .. }
+        | Self::ParsingError { path, .. }
+        | Self::InvalidUtf8 { path, .. }
+        | Self::ValidationError { path, .. } = &mut self
+        {
+            path.push(segment);
+        }
+
+        self.advance(offset)
+    }
+
+    /// Records `name` as the outermost type the failing value is part of.
+    pub fn in_type(mut self, name: &'static str) -> Self {
+        if let Self::NotEnoughData { path, .. }
+        | Self::ParsingError { path, .. }
+        | Self::InvalidUtf8 { path, .. }
+        | Self::ValidationError { path, .. } = &mut self
+        {
+            path.set_root(name);
+        }
+
+        self
+    }
+}
+
+/// Text of a `ParsingError` or `ValidationError`.
+///
+/// Without the `alloc` feature errors can only carry static text, so messages that are
+/// formatted with `alloc` are replaced by their format string.
+#[cfg(feature = "alloc")]
+pub type ErrorMessage = alloc::string::String;
+
+/// Text of a `ParsingError` or `ValidationError`.
+///
+/// Without the `alloc` feature errors can only carry static text, so messages that are
+/// formatted with `alloc` are replaced by their format string.
+#[cfg(not(feature = "alloc"))]
+pub type ErrorMessage = &'static str;
+
+/// Displays where in the value an error happened, if known.
+struct Location<'a>(&'a FieldPath);
+
+impl fmt::Display for Location<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.is_empty() {
+            true => Ok(()),
+            false => write!(f, " of `{}`", self.0),
+        }
+    }
+}
+
+fn location(path: &FieldPath) -> Location<'_> {
+    Location(path)
+}
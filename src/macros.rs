@@ -1,23 +1,84 @@
+/// Implements `Sirius` for number types. Types followed by `as u128` or `as i128` are integers
+/// that are widened to that type and written as varints under [`IntEncoding::Varint`].
+///
+/// [`IntEncoding::Varint`]: crate::IntEncoding::Varint
 #[macro_export]
 macro_rules! impl_sirius_for_numbers {
-    [ $($t:ty),+ $(,)? ] => {
+    [ $($t:ty => $primitive:ident $(as $wide:ty)?),+ $(,)? ] => {
         $(
+            impl SiriusSchema for $t {
+                #[cfg(feature = "alloc")]
+                fn describe(_context: &mut schema::SchemaContext) -> schema::Schema {
+                    schema::Schema::Primitive(schema::Primitive::$primitive)
+                }
+            }
+
             impl Sirius for $t {
-                fn serialize(&self, output: &mut impl std::io::Write) -> Result<usize, SiriusError> {
-                    output.write_all(&self.to_be_bytes())?;
-                    Ok(std::mem::size_of::<Self>())
-                }
-
-                fn deserialize(data: &[u8]) -> Result<(Self, usize), SiriusError> {
-                    Ok((
-                        Self::from_be_bytes(
-                            data.get(..std::mem::size_of::<Self>())
-                                .ok_or(SiriusError::NotEnoughData)?
-                                .try_into()
-                                .expect("slice length is std::mem::size_of::<Self>() bytes"),
-                        ),
-                        std::mem::size_of::<Self>(),
-                    ))
+                default_config_methods!();
+
+                fn serialize_with(&self, output: &mut impl Writer, config: &Config) -> Result<usize, SiriusError> {
+                    $(
+                        if config.int_encoding == IntEncoding::Varint {
+                            return varint::serialize_int(*self as $wide, output);
+                        }
+                    )?
+
+                    match config.byte_order.is_big_endian() {
+                        true => output.write_all(&self.to_be_bytes())?,
+                        false => output.write_all(&self.to_le_bytes())?,
+                    }
+                    Ok(core::mem::size_of::<Self>())
+                }
+
+                fn encoded_len_with(&self, config: &Config) -> usize {
+                    $(
+                        if config.int_encoding == IntEncoding::Varint {
+                            return varint::encoded_len_int(*self as $wide);
+                        }
+                    )?
+
+                    _ = config;
+                    core::mem::size_of::<Self>()
+                }
+
+                fn deserialize_with(data: &[u8], config: &Config) -> Result<(Self, usize), SiriusError> {
+                    $(
+                        if config.int_encoding == IntEncoding::Varint {
+                            let (value, len) = varint::deserialize_int::<$wide>(data, Self::BITS)?;
+                            return Ok((value as Self, len));
+                        }
+                    )?
+
+                    let size = core::mem::size_of::<Self>();
+                    let bytes = data
+                        .get(..size)
+                        .ok_or_else(|| SiriusError::not_enough_data(size, data.len()))?
+                        .try_into()
+                        .expect("slice length is core::mem::size_of::<Self>() bytes");
+
+                    let value = match config.byte_order.is_big_endian() {
+                        true => Self::from_be_bytes(bytes),
+                        false => Self::from_le_bytes(bytes),
+                    };
+
+                    Ok((value, size))
+                }
+
+                #[cfg(feature = "std")]
+                fn deserialize_from_with(
+                    reader: &mut impl std::io::Read,
+                    config: &Config,
+                ) -> Result<(Self, usize), SiriusError> {
+                    $(
+                        if config.int_encoding == IntEncoding::Varint {
+                            let (value, len) = varint::deserialize_int_from::<$wide>(reader, Self::BITS)?;
+                            return Ok((value as Self, len));
+                        }
+                    )?
+
+                    let mut buf = [0; core::mem::size_of::<Self>()];
+                    read::read_exact(reader, &mut buf)?;
+                    Self::deserialize_with(&buf, config)
                 }
             }
         )+
@@ -32,7 +93,148 @@ macro_rules! impl_sirius_for_numbers {
 
                 assert_eq!(n, m);
                 assert_eq!(bytes_read, v.len());
+                assert_eq!(n.encoded_len(), v.len());
+                assert_eq!(<$t as Sirius>::deserialize_from(&mut &v[..]).unwrap(), (n, v.len()));
+
+                let config = Config::new().byte_order(ByteOrder::Little);
+                let mut v = vec![];
+                n.serialize_with(&mut v, &config).unwrap();
+                assert_eq!(v, n.to_le_bytes());
+                assert_eq!(<$t as Sirius>::deserialize_with(&v, &config).unwrap(), (n, v.len()));
+                assert_eq!(<$t as Sirius>::deserialize_from_with(&mut &v[..], &config).unwrap(), (n, v.len()));
+
+                let config = Config::new().int_encoding(IntEncoding::Varint);
+                let mut v = vec![];
+                n.serialize_with(&mut v, &config).unwrap();
+                assert_eq!(n.encoded_len_with(&config), v.len());
+                assert_eq!(<$t as Sirius>::deserialize_with(&v, &config).unwrap(), (n, v.len()));
+                assert_eq!(<$t as Sirius>::deserialize_from_with(&mut &v[..], &config).unwrap(), (n, v.len()));
             )+
         }
     };
 }
+
+/// Implements [`SiriusEncode`] and [`SiriusDe`] for [`Sirius`] types by calling their
+/// [`Sirius`] methods, for hand-written [`Sirius`] impls of types that are part of borrowing
+/// ones. Generic types list their parameters in braces:
+///
+/// ```
+/// # use sirius::{Sirius, SiriusSchema};
+/// # struct Id(u32);
+/// # struct Tagged<T>(T);
+/// # impl SiriusSchema for Id {}
+/// # impl<T> SiriusSchema for Tagged<T> {}
+/// # impl Sirius for Id {
+/// #     fn serialize(&self, output: &mut impl sirius::Writer) -> Result<usize, sirius::SiriusError> { self.0.serialize(output) }
+/// #     fn deserialize(data: &[u8]) -> Result<(Self, usize), sirius::SiriusError> { u32::deserialize(data).map(|(id, len)| (Id(id), len)) }
+/// # }
+/// # impl<T: Sirius> Sirius for Tagged<T> {
+/// #     fn serialize(&self, output: &mut impl sirius::Writer) -> Result<usize, sirius::SiriusError> { self.0.serialize(output) }
+/// #     fn deserialize(data: &[u8]) -> Result<(Self, usize), sirius::SiriusError> { T::deserialize(data).map(|(t, len)| (Tagged(t), len)) }
+/// # }
+/// sirius::impl_borrow_via_sirius!(Id, {T: Sirius} Tagged<T>);
+/// ```
+///
+/// [`Sirius`]: crate::Sirius
+/// [`SiriusEncode`]: crate::SiriusEncode
+/// [`SiriusDe`]: crate::SiriusDe
+#[macro_export]
+macro_rules! impl_borrow_via_sirius {
+    ($($({$($generics:tt)*})? $ty:ty),+ $(,)?) => {
+        $(
+            impl$(<$($generics)*>)? $crate::SiriusEncode for $ty {
+                fn encode_with(
+                    &self,
+                    output: &mut impl $crate::Writer,
+                    config: &$crate::Config,
+                ) -> ::core::result::Result<usize, $crate::SiriusError> {
+                    $crate::Sirius::serialize_with(self, output, config)
+                }
+
+                fn encode_len_with(&self, config: &$crate::Config) -> usize {
+                    $crate::Sirius::encoded_len_with(self, config)
+                }
+            }
+
+            impl<'de, $($($generics)*)?> $crate::SiriusDe<'de> for $ty {
+                fn deserialize_borrowed_with(
+                    data: &'de [u8],
+                    config: &$crate::Config,
+                ) -> ::core::result::Result<(Self, usize), $crate::SiriusError> {
+                    <Self as $crate::Sirius>::deserialize_with(data, config)
+                }
+            }
+        )+
+    };
+}
+
+/// Implements `serialize`, `deserialize` and `deserialize_from` of a `Sirius` impl by calling
+/// their `_with` counterparts with the default [`Config`].
+macro_rules! default_config_methods {
+    () => {
+        fn serialize(&self, output: &mut impl Writer) -> Result<usize, SiriusError> {
+            self.serialize_with(output, &Config::default())
+        }
+
+        fn deserialize(data: &[u8]) -> Result<(Self, usize), SiriusError> {
+            Self::deserialize_with(data, &Config::default())
+        }
+
+        #[cfg(feature = "std")]
+        fn deserialize_from(reader: &mut impl std::io::Read) -> Result<(Self, usize), SiriusError> {
+            Self::deserialize_from_with(reader, &Config::default())
+        }
+    };
+}
+
+pub(crate) use default_config_methods;
+
+/// Expands to the items it is given if `sirius` is built with the `alloc` feature, used by the
+/// derive for code that needs `alloc`.
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __alloc {
+    ($($item:item)*) => { $($item)* };
+}
+
+#[cfg(not(feature = "alloc"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __alloc {
+    ($($item:item)*) => {};
+}
+
+/// Same as [`__alloc!`] for the `std` feature.
+#[cfg(feature = "std")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __std {
+    ($($item:item)*) => { $($item)* };
+}
+
+#[cfg(not(feature = "std"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __std {
+    ($($item:item)*) => {};
+}
+
+/// An [`ErrorMessage`](crate::ErrorMessage) formatted like `format!`, or only the format
+/// string without the `alloc` feature.
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __message {
+    ($($arg:tt)*) => { $crate::__private::format!($($arg)*) };
+}
+
+#[cfg(not(feature = "alloc"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __message {
+    ($format:literal $(, $arg:expr)* $(,)?) => {{
+        $(let _ = &$arg;)*
+        $format
+    }};
+}
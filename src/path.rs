@@ -0,0 +1,106 @@
+//! Location of a failing value inside the value being decoded, see
+//! [`SiriusError`](crate::SiriusError).
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Path from the outermost type being decoded to the value that failed to decode, displayed
+/// as e.g. `Order.items[3].sku`.
+///
+/// The path is built while the error propagates up, so decoding that succeeds never touches it.
+/// Without the `alloc` feature only the root is recorded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldPath {
+    root: Option<&'static str>,
+
+    /// Innermost segment first, in the order they are pushed.
+    #[cfg(feature = "alloc")]
+    segments: Vec<PathSegment>,
+}
+
+/// One step of a [`FieldPath`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathSegment {
+    /// A named field, or the index of a field of a tuple struct or variant.
+    Field(&'static str),
+
+    /// An enum variant.
+    Variant(&'static str),
+
+    /// An element of a sequence or array.
+    Index(usize),
+}
+
+impl FieldPath {
+    /// Name of the outermost derived type the failing value is part of.
+    pub fn root(&self) -> Option<&'static str> {
+        self.root
+    }
+
+    /// Segments leading from the root to the failing value.
+    pub fn segments(&self) -> impl DoubleEndedIterator<Item = &PathSegment> {
+        self.innermost_first().iter().rev()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none() && self.innermost_first().is_empty()
+    }
+
+    pub(crate) fn push(&mut self, segment: PathSegment) {
+        #[cfg(feature = "alloc")]
+        self.segments.push(segment);
+        #[cfg(not(feature = "alloc"))]
+        let _ = segment;
+    }
+
+    fn innermost_first(&self) -> &[PathSegment] {
+        #[cfg(feature = "alloc")]
+        return &self.segments;
+        #[cfg(not(feature = "alloc"))]
+        return &[];
+    }
+
+    pub(crate) fn set_root(&mut self, root: &'static str) {
+        self.root = Some(root);
+    }
+}
+
+impl fmt::Display for FieldPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(root) = self.root {
+            f.write_str(root)?;
+        }
+
+        for segment in self.segments() {
+            match segment {
+                PathSegment::Field(name) => write!(f, ".{name}")?,
+                PathSegment::Variant(name) => write!(f, "::{name}")?,
+                PathSegment::Index(idx) => write!(f, "[{idx}]")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_field_path_display() {
+    let mut path = FieldPath::default();
+    assert!(path.is_empty());
+    assert_eq!(path.to_string(), "");
+
+    path.push(PathSegment::Field("sku"));
+    path.push(PathSegment::Index(3));
+    path.push(PathSegment::Field("items"));
+    assert_eq!(path.to_string(), ".items[3].sku");
+
+    path.set_root("Order");
+    assert_eq!(path.to_string(), "Order.items[3].sku");
+
+    let mut path = FieldPath::default();
+    path.push(PathSegment::Field("0"));
+    path.push(PathSegment::Variant("Circle"));
+    path.set_root("Shape");
+    assert_eq!(path.to_string(), "Shape::Circle.0");
+}
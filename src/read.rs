@@ -0,0 +1,106 @@
+//! Helpers for [`Sirius::deserialize_from`](crate::Sirius::deserialize_from).
+
+use std::io::{ErrorKind, Read};
+
+use crate::SiriusError;
+
+/// Fill `buf` from `reader`.
+///
+/// Running out of input is reported as [`SiriusError::NotEnoughData`] with the number of bytes
+/// that could be read, any other failure of the reader as [`SiriusError::IoError`].
+pub fn read_exact(reader: &mut impl Read, buf: &mut [u8]) -> Result<(), SiriusError> {
+    let mut filled = 0;
+
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => return Err(SiriusError::not_enough_data(buf.len(), filled)),
+            Ok(n) => filled += n,
+            Err(error) if error.kind() == ErrorKind::Interrupted => {}
+            Err(error) => return Err(error.into()),
+        }
+    }
+
+    Ok(())
+}
+
+/// Read a value with `deserialize`, which decodes from a slice like [`Sirius::deserialize`].
+///
+/// Bytes are pulled from `reader` as `deserialize` asks for them through the `offset` and
+/// `needed` of the [`SiriusError::NotEnoughData`] it returns, so no more bytes than the value
+/// takes are read. This is how types without their own
+/// [`deserialize_from`](crate::Sirius::deserialize_from) and fields with a custom codec are read.
+///
+/// [`Sirius::deserialize`]: crate::Sirius::deserialize
+pub fn read_with<T>(
+    reader: &mut impl Read,
+    deserialize: impl Fn(&[u8]) -> Result<(T, usize), SiriusError>,
+) -> Result<(T, usize), SiriusError> {
+    let mut data = Vec::new();
+
+    loop {
+        match deserialize(&data) {
+            Err(SiriusError::NotEnoughData {
+                offset,
+                needed,
+                path,
+                ..
+            }) if offset + needed > data.len() => {
+                let end = offset + needed;
+
+                // Read through `take` so that a bogus length does not allocate up front
+                reader
+                    .take((end - data.len()) as u64)
+                    .read_to_end(&mut data)?;
+
+                if data.len() < end {
+                    return Err(SiriusError::NotEnoughData {
+                        offset,
+                        needed,
+                        available: data.len() - offset,
+                        path,
+                    });
+                }
+            }
+            result => return result,
+        }
+    }
+}
+
+#[test]
+fn test_read_exact() {
+    let mut buf = [0; 3];
+    read_exact(&mut &[1, 2, 3, 4][..], &mut buf).unwrap();
+    assert_eq!(buf, [1, 2, 3]);
+
+    assert!(matches!(
+        read_exact(&mut &[1, 2][..], &mut buf),
+        Err(SiriusError::NotEnoughData {
+            needed: 3,
+            available: 2,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_read_with() {
+    use crate::Sirius;
+
+    let data = "hello".to_string().serialize_buffered();
+
+    let mut reader = &data[..];
+    let (value, bytes_read) = read_with(&mut reader, String::deserialize).unwrap();
+    assert_eq!(value, "hello");
+    assert_eq!(bytes_read, data.len());
+    assert!(reader.is_empty());
+
+    let mut reader = &data[..6];
+    assert!(matches!(
+        read_with(&mut reader, String::deserialize),
+        Err(SiriusError::NotEnoughData {
+            needed: 9,
+            available: 6,
+            ..
+        })
+    ));
+}
@@ -0,0 +1,214 @@
+//! Runtime description of the wire layout of [`Sirius`](crate::Sirius) types.
+//!
+//! Describing a type needs the `alloc` feature, without it [`SiriusSchema`] has no methods.
+
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, vec::Vec};
+
+#[cfg(feature = "alloc")]
+use crate::LengthPrefix;
+
+/// A trait for types that can describe how [`Sirius`](crate::Sirius) lays them out in binary.
+///
+/// It is implemented for all built-in types and by `#[derive(Sirius)]`. For hand-written
+/// [`Sirius`](crate::Sirius) impls an empty `impl SiriusSchema for T {}` describes the type as
+/// [`Schema::Opaque`].
+pub trait SiriusSchema {
+    /// Describe the wire layout of the type.
+    #[cfg(feature = "alloc")]
+    fn schema() -> Schema
+    where
+        Self: Sized,
+    {
+        Self::describe(&mut SchemaContext::default())
+    }
+
+    /// Describe the wire layout of the type, `context` tracks the structs and enums being
+    /// described to stop recursive types from recursing forever.
+    #[cfg(feature = "alloc")]
+    fn describe(context: &mut SchemaContext) -> Schema {
+        _ = context;
+        Schema::Opaque(core::any::type_name::<Self>())
+    }
+}
+
+#[cfg(feature = "alloc")]
+/// Wire layout of a type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schema {
+    /// A number, written with the byte order and integer encoding of the [`Config`].
+    ///
+    /// [`Config`]: crate::Config
+    Primitive(Primitive),
+
+    /// A `char` encoded as 1 to 4 bytes of UTF-8.
+    Char,
+
+    /// UTF-8 bytes preceded by their length, written as the [`LengthPrefix`] of the
+    /// [`Config`].
+    ///
+    /// [`LengthPrefix`]: crate::LengthPrefix
+    /// [`Config`]: crate::Config
+    String,
+
+    /// Elements preceded by their count, written like the length of a [`Schema::String`].
+    Sequence(Box<Schema>),
+
+    /// A fixed number of elements, without a length.
+    Array {
+        len: usize,
+        element: Box<Schema>,
+    },
+
+    /// A value that takes no bytes.
+    Unit,
+
+    Struct(StructSchema),
+    Enum(EnumSchema),
+
+    /// A struct or enum of the given name that is being described further up the tree.
+    Recursive(&'static str),
+
+    /// A value written by custom code, named by its type or by the codec writing it.
+    Opaque(&'static str),
+}
+
+/// Number types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Primitive {
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    F32,
+    F64,
+    Usize,
+    Isize,
+}
+
+impl Primitive {
+    /// Number of bytes the type takes.
+    pub fn size(self) -> usize {
+        match self {
+            Self::U8 | Self::I8 => 1,
+            Self::U16 | Self::I16 => 2,
+            Self::U32 | Self::I32 | Self::F32 => 4,
+            Self::U64 | Self::I64 | Self::F64 => 8,
+            Self::U128 | Self::I128 => 16,
+            Self::Usize | Self::Isize => core::mem::size_of::<usize>(),
+        }
+    }
+
+    /// Whether the type is written as a varint under
+    /// [`IntEncoding::Varint`](crate::IntEncoding::Varint), i.e. it is an integer wider than a
+    /// byte.
+    pub fn is_varint(self) -> bool {
+        !matches!(self, Self::U8 | Self::I8 | Self::F32 | Self::F64)
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructSchema {
+    pub name: &'static str,
+
+    /// Version written as a varint before the fields, set by `#[sirius(version = N)]`.
+    pub version: Option<u32>,
+
+    pub fields: Vec<FieldSchema>,
+}
+
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnumSchema {
+    pub name: &'static str,
+    pub tag_type: TagType,
+
+    /// `tag_type` was picked by the derive as the smallest of `u8`, `u16` and `u32` that fits
+    /// the largest tag, and grows when a larger tag is added. Writing it out with
+    /// `#[sirius(tag_type = "...")]` pins it.
+    pub auto_tag_type: bool,
+
+    pub variants: Vec<VariantSchema>,
+}
+
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariantSchema {
+    pub name: &'static str,
+
+    /// Tag written before the variant's fields.
+    pub tag: u64,
+
+    pub fields: Vec<FieldSchema>,
+}
+
+#[cfg(feature = "alloc")]
+/// A field that is written, skipped fields are left out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldSchema {
+    /// Name of the field, or its index for tuple structs and variants.
+    pub name: &'static str,
+
+    /// First struct version the field is part of.
+    pub since: Option<u32>,
+
+    /// First struct version the field is no longer part of.
+    pub until: Option<u32>,
+
+    /// The field is written with [`IntEncoding::Varint`](crate::IntEncoding::Varint) whatever
+    /// the configuration, set by `#[sirius(varint)]`.
+    pub varint: bool,
+
+    /// Length prefix the field is written with instead of the configured one, set by
+    /// `#[sirius(len = "...")]`.
+    pub length_prefix: Option<LengthPrefix>,
+
+    pub schema: Schema,
+}
+
+/// How enum variant tags are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagType {
+    U8,
+    U16,
+    U32,
+
+    /// LEB128, see [`varint`](crate::varint).
+    Varint,
+}
+
+#[cfg(feature = "alloc")]
+/// Tracks the structs and enums that are being described, see [`SiriusSchema::describe`].
+#[derive(Debug, Default)]
+pub struct SchemaContext {
+    in_progress: Vec<&'static str>,
+}
+
+#[cfg(feature = "alloc")]
+impl SchemaContext {
+    /// Describe the struct or enum identified by `type_name` with `describe`, or return
+    /// [`Schema::Recursive`] if it is already being described.
+    pub fn named(
+        &mut self,
+        type_name: &'static str,
+        name: &'static str,
+        describe: impl FnOnce(&mut Self) -> Schema,
+    ) -> Schema {
+        if self.in_progress.contains(&type_name) {
+            return Schema::Recursive(name);
+        }
+
+        self.in_progress.push(type_name);
+        let schema = describe(self);
+        self.in_progress.pop();
+
+        schema
+    }
+}
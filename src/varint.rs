@@ -0,0 +1,305 @@
+//! LEB128 variable-length encoding of unsigned integers.
+//!
+//! Every byte stores 7 bits of the value, least significant group first, and has its high bit
+//! set if more bytes follow, so values below 128 take a single byte.
+
+use crate::{ErrorMessage, SiriusError, Writer};
+
+/// Maximum number of bytes a `u64` takes when encoded.
+pub const MAX_BYTES: usize = 10;
+
+/// Maximum number of bytes a `u128` takes when encoded.
+pub(crate) const MAX_BYTES_128: usize = 19;
+
+/// Write `value` to output and return the number of bytes written.
+pub fn serialize(value: u64, output: &mut impl Writer) -> Result<usize, SiriusError> {
+    serialize_int(value as u128, output)
+}
+
+/// Number of bytes [`serialize`] writes for `value`.
+pub fn encoded_len(value: u64) -> usize {
+    encoded_len_int(value as u128)
+}
+
+/// Read a value and return the number of bytes read.
+///
+/// Overlong encodings (with redundant trailing zero groups) and encodings of values that do not
+/// fit in a `u64` are rejected, so every value has exactly one valid encoding.
+pub fn deserialize(data: &[u8]) -> Result<(u64, usize), SiriusError> {
+    decode(data, u64::BITS).map(|(value, len)| (value as u64, len))
+}
+
+/// Read a value from `reader`, one byte at a time, and return the number of bytes read.
+#[cfg(feature = "std")]
+pub fn deserialize_from(reader: &mut impl std::io::Read) -> Result<(u64, usize), SiriusError> {
+    decode_from(reader, u64::BITS).map(|(value, len)| (value as u64, len))
+}
+
+/// Integers are widened to `u128` or `i128` to be written by
+/// [`IntEncoding::Varint`](crate::IntEncoding::Varint). Signed ones are zigzag-encoded first,
+/// which interleaves negative and positive numbers (0, -1, 1, -2, ...) so that numbers close to
+/// zero stay short.
+pub(crate) trait Zigzag: Sized {
+    fn zigzag(self) -> u128;
+    fn unzigzag(value: u128) -> Self;
+}
+
+impl Zigzag for u128 {
+    fn zigzag(self) -> u128 {
+        self
+    }
+
+    fn unzigzag(value: u128) -> Self {
+        value
+    }
+}
+
+impl Zigzag for i128 {
+    fn zigzag(self) -> u128 {
+        ((self << 1) ^ (self >> (i128::BITS - 1))) as u128
+    }
+
+    fn unzigzag(value: u128) -> Self {
+        (value >> 1) as i128 ^ -((value & 1) as i128)
+    }
+}
+
+/// Write a widened integer, see [`Zigzag`].
+pub(crate) fn serialize_int<T: Zigzag>(
+    value: T,
+    output: &mut impl Writer,
+) -> Result<usize, SiriusError> {
+    let mut buf = [0u8; MAX_BYTES_128];
+    let len = encode(value.zigzag(), &mut buf);
+    output.write_all(&buf[..len])?;
+    Ok(len)
+}
+
+/// Number of bytes [`serialize_int`] writes for `value`.
+pub(crate) fn encoded_len_int<T: Zigzag>(value: T) -> usize {
+    let bits = u128::BITS - value.zigzag().leading_zeros();
+    (bits as usize).div_ceil(7).max(1)
+}
+
+/// Read a widened integer of a type that is `bits` wide, rejecting values that do not fit in it.
+pub(crate) fn deserialize_int<T: Zigzag>(
+    data: &[u8],
+    bits: u32,
+) -> Result<(T, usize), SiriusError> {
+    decode(data, bits).map(|(value, len)| (T::unzigzag(value), len))
+}
+
+/// Same as [`deserialize_int`], reading from `reader` one byte at a time.
+#[cfg(feature = "std")]
+pub(crate) fn deserialize_int_from<T: Zigzag>(
+    reader: &mut impl std::io::Read,
+    bits: u32,
+) -> Result<(T, usize), SiriusError> {
+    decode_from(reader, bits).map(|(value, len)| (T::unzigzag(value), len))
+}
+
+/// Encode `value` into the start of `buf` and return the number of bytes used.
+fn encode(mut value: u128, buf: &mut [u8; MAX_BYTES_128]) -> usize {
+    let mut len = 0;
+
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            buf[len] = byte;
+            return len + 1;
+        }
+
+        buf[len] = byte | 0x80;
+        len += 1;
+    }
+}
+
+/// Decode a value that fits in `bits` bits.
+fn decode(data: &[u8], bits: u32) -> Result<(u128, usize), SiriusError> {
+    let max_bytes = bits.div_ceil(7) as usize;
+    let mut value = 0;
+
+    for (idx, &byte) in data.iter().enumerate().take(max_bytes) {
+        let group = (byte & 0x7f) as u128;
+        let shift = idx as u32 * 7;
+
+        if bits - shift < 7 && group >> (bits - shift) != 0 {
+            return Err(too_wide(bits));
+        }
+
+        value |= group << shift;
+
+        if byte & 0x80 == 0 {
+            if byte == 0 && idx != 0 {
+                return Err(invalid("overlong encoding"));
+            }
+
+            return Ok((value, idx + 1));
+        }
+    }
+
+    if data.len() < max_bytes {
+        Err(SiriusError::not_enough_data(data.len() + 1, data.len()))
+    } else {
+        Err(too_wide(bits))
+    }
+}
+
+#[cfg(feature = "std")]
+fn decode_from(reader: &mut impl std::io::Read, bits: u32) -> Result<(u128, usize), SiriusError> {
+    let max_bytes = bits.div_ceil(7) as usize;
+    let mut buf = [0u8; MAX_BYTES_128];
+
+    for idx in 0..max_bytes {
+        crate::read::read_exact(reader, &mut buf[idx..=idx]).map_err(|error| error.advance(idx))?;
+
+        if buf[idx] & 0x80 == 0 {
+            return decode(&buf[..=idx], bits);
+        }
+    }
+
+    decode(&buf[..max_bytes], bits)
+}
+
+fn too_wide(bits: u32) -> SiriusError {
+    invalid(crate::__message!(
+        "encoded value does not fit in {} bits",
+        bits
+    ))
+}
+
+fn invalid(error: impl Into<ErrorMessage>) -> SiriusError {
+    SiriusError::parsing_error("varint", error)
+}
+
+#[test]
+fn test_varint_roundtrip() {
+    for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX - 1, u64::MAX] {
+        let mut serialized = vec![];
+        let bytes_written = serialize(value, &mut serialized).unwrap();
+
+        assert_eq!(bytes_written, serialized.len());
+        assert_eq!(encoded_len(value), serialized.len());
+        assert_eq!(deserialize(&serialized).unwrap(), (value, serialized.len()));
+    }
+
+    let mut serialized = vec![];
+    serialize(300, &mut serialized).unwrap();
+    assert_eq!(serialized, vec![0xac, 0x02]);
+}
+
+#[test]
+fn test_varint_deserialize_from() {
+    let mut serialized = vec![];
+    serialize(300, &mut serialized).unwrap();
+    serialized.push(0xff);
+
+    let mut reader = &serialized[..];
+    assert_eq!(deserialize_from(&mut reader).unwrap(), (300, 2));
+    assert_eq!(reader, [0xff]);
+
+    assert!(matches!(
+        deserialize_from(&mut &[0x80][..]),
+        Err(SiriusError::NotEnoughData {
+            offset: 1,
+            needed: 1,
+            available: 0,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_varint_rejects_invalid() {
+    assert!(matches!(
+        deserialize(&[]),
+        Err(SiriusError::NotEnoughData {
+            needed: 1,
+            available: 0,
+            ..
+        })
+    ));
+    assert!(matches!(
+        deserialize(&[0x80]),
+        Err(SiriusError::NotEnoughData {
+            needed: 2,
+            available: 1,
+            ..
+        })
+    ));
+
+    // 0 encoded with a redundant continuation group
+    assert!(matches!(
+        deserialize(&[0x80, 0x00]),
+        Err(SiriusError::ParsingError {
+            ty_name: "varint",
+            ..
+        })
+    ));
+
+    // 2^64, one past u64::MAX
+    let overflow = [0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x02];
+    assert!(matches!(
+        deserialize(&overflow),
+        Err(SiriusError::ParsingError { .. })
+    ));
+
+    // eleven bytes
+    let too_long = [0xff; 11];
+    assert!(matches!(
+        deserialize(&too_long),
+        Err(SiriusError::ParsingError { .. })
+    ));
+}
+
+#[test]
+fn test_varint_int() {
+    let mut serialized = vec![];
+    for value in [0i128, -1, 1, -2, 63, -64, 64] {
+        serialize_int(value, &mut serialized).unwrap();
+    }
+    assert_eq!(serialized, [0, 1, 2, 3, 0x7e, 0x7f, 0x80, 0x01]);
+
+    for value in [i16::MIN as i128, i16::MAX as i128, i128::MIN, i128::MAX] {
+        let mut serialized = vec![];
+        serialize_int(value, &mut serialized).unwrap();
+
+        let bits = if value.unsigned_abs() > 1 << 15 {
+            128
+        } else {
+            16
+        };
+        assert_eq!(
+            deserialize_int::<i128>(&serialized, bits).unwrap(),
+            (value, serialized.len())
+        );
+    }
+
+    let mut serialized = vec![];
+    serialize_int(u128::MAX, &mut serialized).unwrap();
+    assert_eq!(serialized.len(), MAX_BYTES_128);
+    assert_eq!(
+        deserialize_int::<u128>(&serialized, 128).unwrap(),
+        (u128::MAX, MAX_BYTES_128)
+    );
+    assert_eq!(
+        deserialize_int_from::<u128>(&mut &serialized[..], 128).unwrap(),
+        (u128::MAX, MAX_BYTES_128)
+    );
+
+    // 2^16 does not fit in a u16, neither does anything longer than 3 bytes
+    assert!(matches!(
+        deserialize_int::<u128>(&[0x80, 0x80, 0x04], 16),
+        Err(SiriusError::ParsingError { .. })
+    ));
+    assert!(matches!(
+        deserialize_int::<u128>(&[0xff, 0xff, 0x83, 0x00], 16),
+        Err(SiriusError::ParsingError { .. })
+    ));
+    assert_eq!(
+        deserialize_int::<u128>(&[0xff, 0xff, 0x03], 16).unwrap(),
+        (u16::MAX as u128, 3)
+    );
+}
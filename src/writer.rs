@@ -0,0 +1,84 @@
+//! Destinations of [`Sirius::serialize`](crate::Sirius::serialize).
+
+use crate::SiriusError;
+
+/// Something serialized bytes can be written to.
+///
+/// Implemented for `Vec<u8>` with the `alloc` feature and for `&mut [u8]`, which is filled from
+/// the front and shrinks to the part that is left, like `std::io::Write` does for it. Any
+/// `std::io::Write` can be written to through an [`IoWriter`].
+pub trait Writer {
+    /// Write all of `bytes`.
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), SiriusError>;
+}
+
+#[cfg(feature = "alloc")]
+impl Writer for alloc::vec::Vec<u8> {
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), SiriusError> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// Nothing is written if `bytes` does not fit, the error is a
+/// [`SiriusError::BufferTooSmall`].
+impl Writer for &mut [u8] {
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), SiriusError> {
+        if bytes.len() > self.len() {
+            return Err(SiriusError::BufferTooSmall {
+                needed: bytes.len(),
+                available: self.len(),
+            });
+        }
+
+        let (written, rest) = core::mem::take(self).split_at_mut(bytes.len());
+        written.copy_from_slice(bytes);
+        *self = rest;
+
+        Ok(())
+    }
+}
+
+/// Counts the bytes written to it without storing them.
+///
+/// Used by the default [`Sirius::encoded_len_with`](crate::Sirius::encoded_len_with).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ByteCounter(pub usize);
+
+impl Writer for ByteCounter {
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), SiriusError> {
+        self.0 += bytes.len();
+        Ok(())
+    }
+}
+
+/// Writes to a [`std::io::Write`], failures of which are [`SiriusError::IoError`]s.
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct IoWriter<W>(pub W);
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> Writer for IoWriter<W> {
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), SiriusError> {
+        Ok(self.0.write_all(bytes)?)
+    }
+}
+
+#[test]
+fn test_slice_writer() {
+    let mut buf = [0; 5];
+    let mut output = &mut buf[..];
+
+    output.write_all(&[1, 2, 3]).unwrap();
+    assert!(matches!(
+        output.write_all(&[4, 5, 6]),
+        Err(SiriusError::BufferTooSmall {
+            needed: 3,
+            available: 2,
+        })
+    ));
+    output.write_all(&[4, 5]).unwrap();
+
+    assert!(output.is_empty());
+    assert_eq!(buf, [1, 2, 3, 4, 5]);
+}
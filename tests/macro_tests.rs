@@ -88,3 +88,1505 @@ fn test_enum_sirius() {
     assert_eq!(deserialized_c, original_c);
     assert_eq!(bytes_read_a + bytes_read_b + bytes_read_c, serialized.len());
 }
+
+#[test]
+fn test_generic_struct_sirius() {
+    #[derive(Sirius, Debug, PartialEq)]
+    struct Envelope<T> {
+        id: u32,
+        body: T,
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    struct Pair<'a, A, B: Clone, const N: usize>(A, [B; N], std::marker::PhantomData<&'a ()>)
+    where
+        A: Copy;
+
+    let original = Envelope {
+        id: 7,
+        body: "Hello".to_string(),
+    };
+
+    let serialized = original.serialize_buffered();
+    assert_eq!(
+        serialized,
+        vec![0, 0, 0, 7, 0, 0, 0, 5, 72, 101, 108, 108, 111]
+    );
+
+    let (deserialized, bytes_read) = Envelope::<String>::deserialize(&serialized).unwrap();
+    assert_eq!(deserialized, original);
+    assert_eq!(bytes_read, serialized.len());
+
+    let _ = Pair::<u8, u16, 2>::deserialize;
+}
+
+#[test]
+fn test_generic_enum_sirius() {
+    #[derive(Sirius, Debug, PartialEq)]
+    enum Reply<E> {
+        Ok,
+        Err(E),
+        Both(u8, E),
+    }
+
+    let original = Reply::Both(1, 2u16);
+    let serialized = original.serialize_buffered();
+    assert_eq!(serialized, vec![2, 1, 0, 2]);
+
+    let (deserialized, bytes_read) = Reply::<u16>::deserialize(&serialized).unwrap();
+    assert_eq!(deserialized, original);
+    assert_eq!(bytes_read, serialized.len());
+
+    assert_eq!(Reply::<u16>::Ok.serialize_buffered(), vec![0]);
+    assert_eq!(Reply::Err(3u8).serialize_buffered(), vec![1, 3]);
+}
+
+#[test]
+fn test_bound_override_sirius() {
+    trait Codec {
+        type Wire: Sirius;
+    }
+
+    struct Raw;
+    impl Codec for Raw {
+        type Wire = u16;
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    #[sirius(bound = "C::Wire: Sirius")]
+    struct Message<C: Codec> {
+        wire: C::Wire,
+    }
+
+    let original = Message::<Raw> { wire: 513 };
+    let serialized = original.serialize_buffered();
+    assert_eq!(serialized, vec![2, 1]);
+
+    let (deserialized, _) = Message::<Raw>::deserialize(&serialized).unwrap();
+    assert_eq!(deserialized.wire, 513);
+}
+
+#[test]
+fn test_skip_sirius() {
+    fn default_hits() -> u32 {
+        3
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    struct Cached {
+        a: u16,
+        #[sirius(skip)]
+        cache: Vec<u32>,
+        #[sirius(skip, default = "default_hits")]
+        hits: u32,
+        #[sirius(skip, default = String::from("unknown"))]
+        label: String,
+        b: u8,
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    enum Event {
+        Tick(#[sirius(skip)] std::time::Duration, u8),
+        Key {
+            code: u16,
+            #[sirius(skip, default = true)]
+            pressed: bool,
+        },
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    struct Unit;
+
+    let original = Cached {
+        a: 1,
+        cache: vec![1, 2, 3],
+        hits: 10,
+        label: "label".into(),
+        b: 2,
+    };
+
+    let serialized = original.serialize_buffered();
+    assert_eq!(serialized, vec![0, 1, 2]);
+
+    let (deserialized, bytes_read) = Cached::deserialize(&serialized).unwrap();
+    assert_eq!(
+        deserialized,
+        Cached {
+            a: 1,
+            cache: vec![],
+            hits: 3,
+            label: "unknown".into(),
+            b: 2,
+        }
+    );
+    assert_eq!(bytes_read, serialized.len());
+
+    let tick = Event::Tick(std::time::Duration::from_secs(1), 4);
+    assert_eq!(tick.serialize_buffered(), vec![0, 4]);
+    assert_eq!(
+        Event::deserialize(&[0, 4]).unwrap().0,
+        Event::Tick(std::time::Duration::ZERO, 4)
+    );
+
+    let key = Event::Key {
+        code: 5,
+        pressed: false,
+    };
+    assert_eq!(key.serialize_buffered(), vec![1, 0, 5]);
+    assert_eq!(
+        Event::deserialize(&[1, 0, 5]).unwrap().0,
+        Event::Key {
+            code: 5,
+            pressed: true
+        }
+    );
+
+    assert_eq!(Unit.serialize_buffered(), Vec::<u8>::new());
+    assert_eq!(Unit::deserialize(&[]).unwrap(), (Unit, 0));
+}
+
+#[test]
+fn test_with_sirius() {
+    use std::net::Ipv4Addr;
+
+    mod ipv4 {
+        use sirius::{Sirius, SiriusError};
+        use std::net::Ipv4Addr;
+
+        pub fn serialize(
+            addr: &Ipv4Addr,
+            output: &mut impl sirius::Writer,
+        ) -> Result<usize, SiriusError> {
+            addr.octets().serialize(output)
+        }
+
+        pub fn deserialize(data: &[u8]) -> Result<(Ipv4Addr, usize), SiriusError> {
+            <[u8; 4]>::deserialize(data).map(|(octets, len)| (Ipv4Addr::from(octets), len))
+        }
+    }
+
+    fn serialize_as_u8(
+        value: &u32,
+        output: &mut impl sirius::Writer,
+    ) -> Result<usize, sirius::SiriusError> {
+        (*value as u8).serialize(output)
+    }
+
+    fn deserialize_doubled(data: &[u8]) -> Result<(u16, usize), sirius::SiriusError> {
+        u16::deserialize(data).map(|(value, len)| (value * 2, len))
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    struct Peer {
+        #[sirius(with = "ipv4")]
+        addr: Ipv4Addr,
+        #[sirius(serialize_with = "serialize_as_u8")]
+        small: u32,
+        #[sirius(deserialize_with = "deserialize_doubled")]
+        doubled: u16,
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    enum Route {
+        Direct(#[sirius(with = "ipv4")] Ipv4Addr),
+    }
+
+    let original = Peer {
+        addr: Ipv4Addr::new(127, 0, 0, 1),
+        small: 9,
+        doubled: 4,
+    };
+
+    let serialized = original.serialize_buffered();
+    assert_eq!(serialized, vec![127, 0, 0, 1, 9, 0, 4]);
+
+    let (addr, bytes_read) = ipv4::deserialize(&serialized).unwrap();
+    assert_eq!((addr, bytes_read), (original.addr, 4));
+
+    let route = Route::Direct(Ipv4Addr::new(10, 0, 0, 1));
+    let serialized = route.serialize_buffered();
+    assert_eq!(serialized, vec![0, 10, 0, 0, 1]);
+    assert_eq!(Route::deserialize(&serialized).unwrap(), (route, 5));
+
+    let deserialized = Peer::deserialize(&[127, 0, 0, 1, 0, 0, 0, 9, 0, 4]).unwrap();
+    assert_eq!(
+        deserialized,
+        (
+            Peer {
+                addr: Ipv4Addr::new(127, 0, 0, 1),
+                small: 9,
+                doubled: 8,
+            },
+            10
+        )
+    );
+}
+
+#[test]
+fn test_enum_tags_sirius() {
+    #[derive(Sirius, Debug, PartialEq)]
+    enum Explicit {
+        #[sirius(tag = 7)]
+        A(u8),
+        B,
+        #[sirius(tag = 2)]
+        C {
+            x: u8,
+        },
+    }
+
+    #[derive(Sirius, Debug, PartialEq, Clone, Copy)]
+    enum Discriminants {
+        Low = 1,
+        Next,
+        High = 200,
+    }
+
+    #[derive(Sirius, Debug, PartialEq, Clone, Copy)]
+    #[repr(u16)]
+    enum Opcode {
+        Nop = 0,
+        Jump = 300,
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    #[sirius(hash_tags)]
+    enum Named {
+        First,
+        #[sirius(tag = 1)]
+        Pinned,
+        Second(u8),
+    }
+
+    assert_eq!(Explicit::A(1).serialize_buffered(), vec![7, 1]);
+    assert_eq!(Explicit::B.serialize_buffered(), vec![8]);
+    assert_eq!(Explicit::C { x: 3 }.serialize_buffered(), vec![2, 3]);
+    assert_eq!(Explicit::deserialize(&[8]).unwrap(), (Explicit::B, 1));
+    assert!(Explicit::deserialize(&[0]).is_err());
+
+    for value in [Discriminants::Low, Discriminants::Next, Discriminants::High] {
+        let serialized = value.serialize_buffered();
+        assert_eq!(serialized, vec![value as u8]);
+        assert_eq!(Discriminants::deserialize(&serialized).unwrap(), (value, 1));
+    }
+
+    let serialized = Opcode::Jump.serialize_buffered();
+    assert_eq!(serialized, 300u16.to_be_bytes());
+    assert_eq!(Opcode::deserialize(&serialized).unwrap(), (Opcode::Jump, 2));
+    assert_eq!(Opcode::Nop.serialize_buffered(), vec![0, 0]);
+
+    // tags only depend on the variant names, so reordering or inserting variants keeps them
+    #[derive(Sirius, Debug, PartialEq)]
+    #[sirius(hash_tags)]
+    enum Reordered {
+        Second(u8),
+        Inserted,
+        First,
+    }
+
+    let first = Named::First.serialize_buffered();
+    let second = Named::Second(5).serialize_buffered();
+    assert_eq!(Named::Pinned.serialize_buffered(), vec![1]);
+    assert_eq!(Reordered::First.serialize_buffered(), first);
+    assert_eq!(Reordered::Second(5).serialize_buffered(), second);
+    assert_eq!(
+        Reordered::deserialize(&second).unwrap(),
+        (Reordered::Second(5), 2)
+    );
+}
+
+#[test]
+fn test_enum_tag_type_sirius() {
+    use sirius::{
+        SiriusSchema,
+        schema::{Schema, TagType},
+    };
+
+    #[derive(Sirius, Debug, PartialEq)]
+    enum Auto {
+        A,
+        #[sirius(tag = 256)]
+        B(u8),
+    }
+
+    // the same tags pinned to the width they would get automatically, and a wider one
+    #[derive(Sirius, Debug, PartialEq)]
+    #[sirius(tag_type = "u16")]
+    enum Pinned {
+        A,
+        #[sirius(tag = 256)]
+        B(u8),
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    #[sirius(tag_type = "u32")]
+    enum Wide {
+        A,
+        B(u8),
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    #[sirius(tag_type = "varint")]
+    enum Varint {
+        A,
+        #[sirius(tag = 300)]
+        B(u8),
+    }
+
+    assert_eq!(Auto::A.serialize_buffered(), vec![0, 0]);
+    assert_eq!(Auto::B(1).serialize_buffered(), vec![1, 0, 1]);
+    assert_eq!(Auto::deserialize(&[1, 0, 1]).unwrap(), (Auto::B(1), 3));
+    assert_eq!(Pinned::B(1).serialize_buffered(), vec![1, 0, 1]);
+
+    // the schema records which tag types were picked automatically, to be pinned
+    let tag_type = |schema| match schema {
+        Schema::Enum(schema) => (schema.tag_type, schema.auto_tag_type),
+        _ => unreachable!(),
+    };
+    assert_eq!(tag_type(Auto::schema()), (TagType::U16, true));
+    assert_eq!(tag_type(Pinned::schema()), (TagType::U16, false));
+    assert_eq!(tag_type(Wide::schema()), (TagType::U32, false));
+
+    assert_eq!(Wide::B(1).serialize_buffered(), vec![0, 0, 0, 1, 1]);
+    assert_eq!(
+        Wide::deserialize(&[0, 0, 0, 1, 1]).unwrap(),
+        (Wide::B(1), 5)
+    );
+
+    assert_eq!(Varint::A.serialize_buffered(), vec![0]);
+    assert_eq!(Varint::B(1).serialize_buffered(), vec![0xac, 0x02, 1]);
+    assert_eq!(
+        Varint::deserialize(&[0xac, 0x02, 1]).unwrap(),
+        (Varint::B(1), 3)
+    );
+    assert!(Varint::deserialize(&[0x80, 0x00]).is_err());
+}
+
+#[test]
+fn test_versioned_sirius() {
+    #[derive(Sirius, Debug, PartialEq)]
+    #[sirius(version = 1)]
+    struct SaveV1 {
+        level: u8,
+        name: String,
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    #[sirius(version = 3)]
+    struct SaveV3 {
+        level: u8,
+        #[sirius(until = 3)]
+        name: String,
+        #[sirius(since = 2, default = 100)]
+        health: u16,
+        #[sirius(since = 3)]
+        tags: Vec<u8>,
+    }
+
+    let v1 = SaveV1 {
+        level: 4,
+        name: "old".into(),
+    };
+    let serialized = v1.serialize_buffered();
+    assert_eq!(serialized, vec![1, 4, 0, 0, 0, 3, 111, 108, 100]);
+
+    // old payloads are read with the new fields defaulted
+    let (upgraded, bytes_read) = SaveV3::deserialize(&serialized).unwrap();
+    assert_eq!(
+        upgraded,
+        SaveV3 {
+            level: 4,
+            name: "old".into(),
+            health: 100,
+            tags: vec![],
+        }
+    );
+    assert_eq!(bytes_read, serialized.len());
+
+    // the removed field is no longer written
+    let v3 = SaveV3 {
+        level: 5,
+        name: "ignored".into(),
+        health: 7,
+        tags: vec![1],
+    };
+    let serialized = v3.serialize_buffered();
+    assert_eq!(serialized, vec![3, 5, 0, 7, 0, 0, 0, 1, 1]);
+
+    let (deserialized, bytes_read) = SaveV3::deserialize(&serialized).unwrap();
+    assert_eq!(
+        deserialized,
+        SaveV3 {
+            name: String::new(),
+            ..v3
+        }
+    );
+    assert_eq!(bytes_read, serialized.len());
+
+    // payloads from the future are rejected
+    assert!(matches!(
+        SaveV1::deserialize(&serialized),
+        Err(sirius::SiriusError::ParsingError {
+            ty_name: "SaveV1",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_schema_sirius() {
+    use sirius::{
+        SiriusSchema,
+        schema::{
+            EnumSchema, FieldSchema, Primitive, Schema, StructSchema, TagType, VariantSchema,
+        },
+    };
+
+    mod codec {
+        pub fn serialize(
+            value: &u8,
+            output: &mut impl sirius::Writer,
+        ) -> Result<usize, sirius::SiriusError> {
+            sirius::Sirius::serialize(value, output)
+        }
+
+        pub fn deserialize(data: &[u8]) -> Result<(u8, usize), sirius::SiriusError> {
+            <u8 as sirius::Sirius>::deserialize(data)
+        }
+    }
+
+    #[derive(Sirius)]
+    #[sirius(version = 2)]
+    struct Header {
+        #[sirius(with = "codec")]
+        kind: u8,
+        #[sirius(skip)]
+        _cache: Vec<u8>,
+        #[sirius(since = 2)]
+        name: String,
+    }
+
+    #[derive(Sirius)]
+    #[sirius(tag_type = "u16")]
+    enum List {
+        Nil,
+        #[sirius(tag = 5)]
+        Cons(u32, Box<List>),
+    }
+
+    let field = |name, since, schema| FieldSchema {
+        name,
+        since,
+        until: None,
+        varint: false,
+        length_prefix: None,
+        schema,
+    };
+
+    assert_eq!(
+        Header::schema(),
+        Schema::Struct(StructSchema {
+            name: "Header",
+            version: Some(2),
+            fields: vec![
+                field("kind", None, Schema::Opaque("codec::serialize")),
+                field("name", Some(2), Schema::String),
+            ],
+        })
+    );
+
+    assert_eq!(
+        List::schema(),
+        Schema::Enum(EnumSchema {
+            name: "List",
+            tag_type: TagType::U16,
+            auto_tag_type: false,
+            variants: vec![
+                VariantSchema {
+                    name: "Nil",
+                    tag: 0,
+                    fields: vec![],
+                },
+                VariantSchema {
+                    name: "Cons",
+                    tag: 5,
+                    fields: vec![
+                        field("0", None, Schema::Primitive(Primitive::U32)),
+                        field("1", None, Schema::Recursive("List")),
+                    ],
+                },
+            ],
+        })
+    );
+}
+
+#[test]
+fn test_transparent_sirius() {
+    use sirius::SiriusSchema;
+
+    #[derive(Sirius, Debug, PartialEq)]
+    #[sirius(transparent)]
+    struct UserId(u64);
+
+    #[derive(Sirius, Debug, PartialEq)]
+    #[sirius(transparent)]
+    struct Tagged<T> {
+        value: T,
+        #[sirius(skip)]
+        marker: std::marker::PhantomData<()>,
+    }
+
+    let id = UserId(42);
+    assert_eq!(id.serialize_buffered(), 42u64.serialize_buffered());
+    assert_eq!(
+        UserId::deserialize(&id.serialize_buffered()).unwrap(),
+        (id, 8)
+    );
+    assert_eq!(UserId::schema(), u64::schema());
+
+    let tagged = Tagged {
+        value: "hi".to_string(),
+        marker: std::marker::PhantomData,
+    };
+    let serialized = tagged.serialize_buffered();
+    assert_eq!(serialized, "hi".to_string().serialize_buffered());
+    assert_eq!(
+        Tagged::<String>::deserialize(&serialized).unwrap(),
+        (tagged, 6)
+    );
+    assert_eq!(Tagged::<String>::schema(), String::schema());
+}
+
+#[test]
+fn test_proxy_sirius() {
+    use sirius::{SiriusError, SiriusSchema};
+
+    #[derive(Sirius)]
+    struct PairDto(u8, u8);
+
+    #[derive(Sirius, Debug, Clone, PartialEq)]
+    #[sirius(into = "PairDto", from = "PairDto")]
+    struct Pair {
+        low: u8,
+        high: u8,
+    }
+
+    impl From<Pair> for PairDto {
+        fn from(pair: Pair) -> Self {
+            Self(pair.high, pair.low)
+        }
+    }
+
+    impl From<PairDto> for Pair {
+        fn from(PairDto(high, low): PairDto) -> Self {
+            Self { low, high }
+        }
+    }
+
+    #[derive(Sirius, Debug, Clone, PartialEq)]
+    #[sirius(into = "u32", try_from = "u32")]
+    struct Even(u32);
+
+    impl From<Even> for u32 {
+        fn from(even: Even) -> Self {
+            even.0
+        }
+    }
+
+    impl TryFrom<u32> for Even {
+        type Error = String;
+
+        fn try_from(value: u32) -> Result<Self, Self::Error> {
+            if value.is_multiple_of(2) {
+                Ok(Self(value))
+            } else {
+                Err(format!("{value} is odd"))
+            }
+        }
+    }
+
+    let pair = Pair { low: 1, high: 2 };
+    let serialized = pair.serialize_buffered();
+    assert_eq!(serialized, vec![2, 1]);
+    assert_eq!(Pair::deserialize(&serialized).unwrap(), (pair, 2));
+    assert_eq!(Pair::schema(), PairDto::schema());
+
+    let even = Even(4);
+    let serialized = even.serialize_buffered();
+    assert_eq!(serialized, 4u32.serialize_buffered());
+    assert_eq!(Even::deserialize(&serialized).unwrap(), (even, 4));
+    assert_eq!(Even::schema(), u32::schema());
+
+    match Even::deserialize(&3u32.serialize_buffered()) {
+        Err(SiriusError::ParsingError { ty_name, error, .. }) => {
+            assert_eq!(ty_name, "Even");
+            assert_eq!(error, "3 is odd");
+        }
+        other => panic!("unexpected result: {other:?}"),
+    }
+}
+
+#[test]
+fn test_validate_sirius() {
+    use sirius::SiriusError;
+
+    fn percentage(value: &u8) -> Result<(), String> {
+        if *value <= 100 {
+            Ok(())
+        } else {
+            Err(format!("{value} is above 100"))
+        }
+    }
+
+    fn ordered(range: &Range) -> Result<(), &'static str> {
+        if range.start <= range.end {
+            Ok(())
+        } else {
+            Err("start is after end")
+        }
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    #[sirius(validate = "ordered")]
+    struct Range {
+        start: u32,
+        end: u32,
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    enum Progress {
+        Done,
+        Running(#[sirius(validate = "percentage")] u8),
+    }
+
+    let range = Range { start: 1, end: 2 };
+    assert_eq!(
+        Range::deserialize(&range.serialize_buffered()).unwrap(),
+        (range, 8)
+    );
+
+    let reversed = Range { start: 2, end: 1 }.serialize_buffered();
+    match Range::deserialize(&reversed) {
+        Err(SiriusError::ValidationError {
+            offset,
+            path,
+            error,
+        }) => {
+            assert_eq!(offset, 0);
+            assert_eq!(path.to_string(), "Range");
+            assert_eq!(error, "start is after end");
+        }
+        other => panic!("unexpected result: {other:?}"),
+    }
+
+    assert_eq!(
+        Progress::deserialize(&[1, 100]).unwrap(),
+        (Progress::Running(100), 2)
+    );
+    assert_eq!(Progress::deserialize(&[0]).unwrap(), (Progress::Done, 1));
+
+    match Progress::deserialize(&[1, 101]) {
+        Err(SiriusError::ValidationError {
+            offset,
+            path,
+            error,
+        }) => {
+            assert_eq!(offset, 1);
+            assert_eq!(path.to_string(), "Progress::Running.0");
+            assert_eq!(error, "101 is above 100");
+        }
+        other => panic!("unexpected result: {other:?}"),
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    struct Plan {
+        name: String,
+        ranges: Vec<Range>,
+    }
+
+    let plan = Plan {
+        name: "a".to_string(),
+        ranges: vec![Range { start: 0, end: 1 }, Range { start: 3, end: 2 }],
+    }
+    .serialize_buffered();
+    match Plan::deserialize(&plan) {
+        Err(error @ SiriusError::ValidationError { offset, .. }) => {
+            // 5 bytes of `name`, 4 of the length of `ranges` and 8 of its first element
+            assert_eq!(offset, 17);
+            assert_eq!(
+                error.to_string(),
+                "invalid value at byte 17 of `Plan.ranges[1]`: start is after end"
+            );
+        }
+        other => panic!("unexpected result: {other:?}"),
+    }
+}
+
+#[test]
+fn test_error_context_sirius() {
+    use sirius::SiriusError;
+
+    #[derive(Sirius, Debug)]
+    struct Item {
+        count: u16,
+        sku: String,
+    }
+
+    #[derive(Sirius, Debug)]
+    enum Shipping {
+        Pickup,
+        Delivery { address: String },
+    }
+
+    #[derive(Sirius, Debug)]
+    struct Order {
+        id: u32,
+        items: Vec<Item>,
+        shipping: Shipping,
+    }
+
+    let order = Order {
+        id: 7,
+        items: vec![
+            Item {
+                count: 1,
+                sku: "a".to_string(),
+            },
+            Item {
+                count: 2,
+                sku: "bcd".to_string(),
+            },
+        ],
+        shipping: Shipping::Delivery {
+            address: "home".to_string(),
+        },
+    };
+    let serialized = order.serialize_buffered();
+
+    // cut in the middle of the second sku: id (4) + length (4) + first item (7) + count (2)
+    let truncated = &serialized[..4 + 4 + 7 + 2 + 5];
+    let error = Order::deserialize(truncated).unwrap_err();
+    match &error {
+        SiriusError::NotEnoughData {
+            offset,
+            needed,
+            available,
+            path,
+        } => {
+            assert_eq!(*offset, 17);
+            assert_eq!(*needed, 7);
+            assert_eq!(*available, 5);
+            assert_eq!(path.to_string(), "Order.items[1].sku");
+            assert_eq!(path.root(), Some("Order"));
+        }
+        other => panic!("unexpected error: {other:?}"),
+    }
+    assert_eq!(
+        error.to_string(),
+        "ran out of data at byte 17 of `Order.items[1].sku`: \
+         needed 7 bytes but only 5 were available"
+    );
+
+    let truncated = &serialized[..serialized.len() - 1];
+    match Order::deserialize(truncated) {
+        Err(SiriusError::NotEnoughData { offset, path, .. }) => {
+            assert_eq!(offset, 4 + 4 + 7 + 9 + 1);
+            assert_eq!(path.to_string(), "Order.shipping::Delivery.address");
+        }
+        other => panic!("unexpected result: {other:?}"),
+    }
+
+    let mut invalid_tag = serialized.clone();
+    invalid_tag[4 + 4 + 7 + 9] = 9;
+    match Order::deserialize(&invalid_tag) {
+        Err(SiriusError::ParsingError {
+            ty_name,
+            offset,
+            path,
+            ..
+        }) => {
+            assert_eq!(ty_name, "Shipping");
+            assert_eq!(offset, 24);
+            assert_eq!(path.to_string(), "Order.shipping");
+        }
+        other => panic!("unexpected result: {other:?}"),
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_deserialize_from_sirius() {
+    use sirius::SiriusError;
+
+    mod fixed_u16 {
+        pub fn serialize(
+            value: &u16,
+            output: &mut impl sirius::Writer,
+        ) -> Result<usize, sirius::SiriusError> {
+            sirius::Sirius::serialize(&(value ^ 0xffff), output)
+        }
+
+        pub fn deserialize(data: &[u8]) -> Result<(u16, usize), sirius::SiriusError> {
+            <u16 as sirius::Sirius>::deserialize(data).map(|(value, n)| (value ^ 0xffff, n))
+        }
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    #[sirius(version = 2)]
+    struct Header {
+        #[sirius(with = "fixed_u16")]
+        kind: u16,
+        #[sirius(since = 2)]
+        name: String,
+        #[sirius(skip)]
+        cached: bool,
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    #[sirius(tag_type = "varint")]
+    enum Message {
+        Ping,
+        Data { header: Header, body: Vec<u8> },
+    }
+
+    let messages = [
+        Message::Data {
+            header: Header {
+                kind: 3,
+                name: "hi".to_string(),
+                cached: false,
+            },
+            body: vec![1, 2, 3],
+        },
+        Message::Ping,
+    ];
+    let mut data = vec![];
+    for message in &messages {
+        message.serialize(&mut data).unwrap();
+    }
+
+    let mut reader = &data[..];
+    for message in messages {
+        let expected_len = message.serialize_buffered().len();
+        assert_eq!(
+            Message::deserialize_from(&mut reader).unwrap(),
+            (message, expected_len)
+        );
+    }
+    assert!(reader.is_empty());
+
+    let mut reader = &data[..9];
+    match Message::deserialize_from(&mut reader) {
+        Err(SiriusError::NotEnoughData { offset, path, .. }) => {
+            assert_eq!(offset, 1 + 1 + 2);
+            assert_eq!(path.to_string(), "Message::Data.header.name");
+        }
+        other => panic!("unexpected result: {other:?}"),
+    }
+}
+
+#[test]
+fn test_decoder_sirius() {
+    use sirius::decoder::{Decoded, Decoder};
+
+    mod raw {
+        pub fn serialize(
+            value: &u32,
+            output: &mut impl sirius::Writer,
+        ) -> Result<usize, sirius::SiriusError> {
+            sirius::Sirius::serialize(value, output)
+        }
+
+        pub fn deserialize(data: &[u8]) -> Result<(u32, usize), sirius::SiriusError> {
+            <u32 as sirius::Sirius>::deserialize(data)
+        }
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    #[sirius(version = 2)]
+    struct Node {
+        #[sirius(until = 2)]
+        legacy: u8,
+        name: String,
+        #[sirius(since = 2)]
+        children: Vec<Node>,
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    #[sirius(tag_type = "varint")]
+    enum Event {
+        Tree(Node),
+        Raw(#[sirius(with = "raw")] u32),
+    }
+
+    fn feed(event: &Event, chunk_len: usize) {
+        let data = event.serialize_buffered();
+        let mut decoder = Decoder::<Event>::new();
+        let mut chunks = data.chunks(chunk_len).peekable();
+
+        while let Some(chunk) = chunks.next() {
+            match decoder.decode(chunk).unwrap() {
+                Decoded::NeedMore { at_least } => {
+                    assert!(chunks.peek().is_some());
+                    assert!(at_least > 0);
+                }
+                Decoded::Complete(decoded, consumed) => {
+                    assert_eq!(&decoded, event);
+                    assert_eq!(consumed, chunk.len());
+                    assert!(chunks.peek().is_none());
+                }
+            }
+        }
+    }
+
+    let tree = Event::Tree(Node {
+        legacy: 0,
+        name: "root".to_string(),
+        children: vec![
+            Node {
+                legacy: 0,
+                name: "a".to_string(),
+                children: vec![],
+            },
+            Node {
+                legacy: 0,
+                name: "b".to_string(),
+                children: vec![],
+            },
+        ],
+    });
+
+    for chunk_len in [1, 2, 3, 7, 100] {
+        feed(&tree, chunk_len);
+        feed(&Event::Raw(7), chunk_len);
+    }
+}
+
+#[test]
+fn test_byte_order_sirius() {
+    use sirius::{ByteOrder, Config};
+
+    #[derive(Sirius, Debug, PartialEq)]
+    #[sirius(tag_type = "u16")]
+    enum Sample {
+        Reading { sensor: u32, values: Vec<i16> },
+        Label(String),
+    }
+
+    let sample = Sample::Reading {
+        sensor: 0x01020304,
+        values: vec![-2],
+    };
+
+    let little = Config::new().byte_order(ByteOrder::Little);
+    let mut data = vec![];
+    sample.serialize_with(&mut data, &little).unwrap();
+    assert_eq!(
+        data,
+        [0, 0, 4, 3, 2, 1, 1, 0, 0, 0, 0xfe, 0xff],
+        "the tag, numbers and length prefix are little-endian"
+    );
+    assert_eq!(
+        Sample::deserialize_with(&data, &little).unwrap(),
+        (sample, data.len())
+    );
+    #[cfg(feature = "std")]
+    assert_eq!(
+        Sample::deserialize_from_with(&mut &data[..], &little)
+            .unwrap()
+            .1,
+        data.len()
+    );
+
+    let label = Sample::Label("hi".to_string());
+    let native = Config::new().byte_order(ByteOrder::Native);
+    let mut data = vec![];
+    label.serialize_with(&mut data, &native).unwrap();
+    assert_eq!(data[..2], 1u16.to_ne_bytes());
+    assert_eq!(
+        Sample::deserialize_with(&data, &native).unwrap(),
+        (label, data.len())
+    );
+
+    // the default configuration is big-endian
+    assert_eq!(
+        Sample::Label("hi".to_string()).serialize_buffered()[..6],
+        [0, 1, 0, 0, 0, 2]
+    );
+}
+
+#[test]
+fn test_varint_sirius() {
+    use sirius::{
+        Config, IntEncoding, SiriusError,
+        decoder::{Decoded, Decoder},
+    };
+
+    #[derive(Sirius, Debug, PartialEq, Clone)]
+    struct Event {
+        #[sirius(varint)]
+        id: u64,
+        #[sirius(varint)]
+        port: u16,
+        #[sirius(varint)]
+        deltas: Vec<i32>,
+        timestamp: u64,
+    }
+
+    let event = Event {
+        id: 300,
+        port: 8080,
+        deltas: vec![-1, 2],
+        timestamp: 7,
+    };
+
+    let data = event.serialize_buffered();
+    assert_eq!(
+        data,
+        [0xac, 0x02, 0x90, 0x3f, 2, 1, 4, 0, 0, 0, 0, 0, 0, 0, 7],
+        "only the fields marked `varint` are varints, with zigzag-encoded signed numbers"
+    );
+    assert_eq!(
+        Event::deserialize(&data).unwrap(),
+        (event.clone(), data.len())
+    );
+    #[cfg(feature = "std")]
+    assert_eq!(
+        Event::deserialize_from(&mut &data[..]).unwrap(),
+        (event.clone(), data.len())
+    );
+
+    let mut decoder = Decoder::<Event>::new();
+    for byte in &data[..data.len() - 1] {
+        assert!(matches!(
+            decoder.decode(&[*byte]),
+            Ok(Decoded::NeedMore { .. })
+        ));
+    }
+    assert_eq!(
+        decoder.decode(&data[data.len() - 1..]).unwrap(),
+        Decoded::Complete(event.clone(), 1)
+    );
+
+    let varint = Config::new().int_encoding(IntEncoding::Varint);
+    let mut data = vec![];
+    event.serialize_with(&mut data, &varint).unwrap();
+    assert_eq!(data, [0xac, 0x02, 0x90, 0x3f, 2, 1, 4, 7]);
+    assert_eq!(
+        Event::deserialize_with(&data, &varint).unwrap(),
+        (event, data.len())
+    );
+
+    // 70000 does not fit in the `u16`
+    let overflow = [0xac, 0x02, 0xf0, 0xa2, 0x04, 0, 7];
+    match Event::deserialize_with(&overflow, &varint) {
+        Err(SiriusError::ParsingError {
+            ty_name: "varint",
+            offset: 2,
+            path,
+            ..
+        }) => assert_eq!(path.to_string(), "Event.port"),
+        other => panic!("unexpected result: {other:?}"),
+    }
+
+    // 0 with a redundant continuation group
+    let overlong = [0x80, 0x00, 0, 0, 7];
+    assert!(matches!(
+        Event::deserialize_with(&overlong, &varint),
+        Err(SiriusError::ParsingError {
+            ty_name: "varint",
+            offset: 0,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_length_prefix_sirius() {
+    use sirius::{
+        Config, LengthPrefix, SiriusError,
+        decoder::{Decoded, Decoder},
+        schema::{FieldSchema, Schema, SiriusSchema, StructSchema},
+    };
+
+    #[derive(Sirius, Debug, PartialEq, Clone)]
+    struct Packet {
+        #[sirius(len = "u8")]
+        name: String,
+        #[sirius(len = "varint")]
+        payload: Vec<u8>,
+        tags: Vec<String>,
+    }
+
+    let packet = Packet {
+        name: "ab".to_string(),
+        payload: vec![7; 3],
+        tags: vec!["x".to_string()],
+    };
+
+    let data = packet.serialize_buffered();
+    assert_eq!(
+        data,
+        [2, b'a', b'b', 3, 7, 7, 7, 0, 0, 0, 1, 0, 0, 0, 1, b'x'],
+        "only the fields without `len` use the configured `u32` prefixes"
+    );
+    assert_eq!(
+        Packet::deserialize(&data).unwrap(),
+        (packet.clone(), data.len())
+    );
+    #[cfg(feature = "std")]
+    assert_eq!(
+        Packet::deserialize_from(&mut &data[..]).unwrap(),
+        (packet.clone(), data.len())
+    );
+
+    let mut decoder = Decoder::<Packet>::new();
+    assert!(matches!(
+        decoder.decode(&data[..data.len() - 1]),
+        Ok(Decoded::NeedMore { at_least: 1 })
+    ));
+    assert_eq!(
+        decoder.decode(&data[data.len() - 1..]).unwrap(),
+        Decoded::Complete(packet.clone(), 1)
+    );
+
+    let u16_prefix = Config::new().length_prefix(LengthPrefix::U16);
+    let mut data = vec![];
+    packet.serialize_with(&mut data, &u16_prefix).unwrap();
+    assert_eq!(data[7..], [0, 1, 0, 1, b'x']);
+    assert_eq!(
+        Packet::deserialize_with(&data, &u16_prefix).unwrap(),
+        (packet, data.len())
+    );
+
+    let Schema::Struct(StructSchema { fields, .. }) = Packet::schema() else {
+        panic!("expected a struct schema");
+    };
+    let prefixes = fields.iter().map(|field: &FieldSchema| field.length_prefix);
+    assert!(prefixes.eq([Some(LengthPrefix::U8), Some(LengthPrefix::Varint), None]));
+
+    let long = Packet {
+        name: "a".repeat(256),
+        payload: vec![],
+        tags: vec![],
+    };
+    assert!(matches!(
+        long.serialize(&mut vec![]),
+        Err(SiriusError::Overflow)
+    ));
+}
+
+#[test]
+fn test_decode_limits_sirius() {
+    use sirius::{Config, DecodeLimits, SiriusError};
+
+    #[derive(Sirius, Debug, PartialEq)]
+    enum List {
+        Nil,
+        Cons(u32, Box<List>),
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    struct Page {
+        title: String,
+        lines: Vec<String>,
+    }
+
+    let mut list = List::Nil;
+    for i in 0..100 {
+        list = List::Cons(i, Box::new(list));
+    }
+    let data = list.serialize_buffered();
+
+    // 101 enums, 100 of them in boxes
+    let config = Config::new().limits(DecodeLimits::new().max_depth(201));
+    assert_eq!(
+        List::deserialize_with(&data, &config).unwrap(),
+        (list, data.len())
+    );
+    let config = Config::new().limits(DecodeLimits::new().max_depth(150));
+    assert!(matches!(
+        List::deserialize_with(&data, &config),
+        Err(SiriusError::LimitExceeded {
+            limit: "max_depth",
+            value: 151,
+            max: 150,
+        })
+    ));
+    #[cfg(feature = "std")]
+    assert!(matches!(
+        List::deserialize_from_with(&mut &data[..], &config),
+        Err(SiriusError::LimitExceeded {
+            limit: "max_depth",
+            ..
+        })
+    ));
+
+    // fields count towards the same total
+    let page = Page {
+        title: "a".repeat(100),
+        lines: vec!["b".repeat(100)],
+    };
+    let data = page.serialize_buffered();
+    let config = Config::new().limits(DecodeLimits::new().max_alloc(150));
+    assert!(matches!(
+        Page::deserialize_with(&data, &config),
+        Err(SiriusError::LimitExceeded {
+            limit: "max_alloc",
+            max: 150,
+            ..
+        })
+    ));
+    #[cfg(feature = "std")]
+    assert!(matches!(
+        Page::deserialize_from_with(&mut &data[..], &config),
+        Err(SiriusError::LimitExceeded {
+            limit: "max_alloc",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_borrow_sirius() {
+    use sirius::{SiriusDe, SiriusEncode, SiriusError};
+    use std::borrow::Cow;
+
+    #[derive(Sirius, Debug, PartialEq)]
+    #[sirius(borrow)]
+    struct Line<'a> {
+        level: u8,
+        host: &'a str,
+        #[sirius(len = "u8")]
+        payload: &'a [u8],
+        message: Cow<'a, str>,
+        #[sirius(skip)]
+        note: &'a str,
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    struct OwnedLine {
+        level: u8,
+        host: String,
+        #[sirius(len = "u8")]
+        payload: Vec<u8>,
+        message: String,
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    #[sirius(borrow)]
+    enum Entry<'a, 'b> {
+        Line(Line<'a>),
+        Raw(Cow<'b, [u8]>),
+        Owned(#[sirius(varint)] u64, String),
+    }
+
+    let line = Line {
+        level: 2,
+        host: "db-1",
+        payload: &[1, 2, 3],
+        message: Cow::Owned("disk full".to_string()),
+        note: "not written",
+    };
+
+    let mut data = vec![];
+    let bytes_written = line.encode(&mut data).unwrap();
+    assert_eq!(bytes_written, data.len());
+
+    // borrowed types are written like their owned counterparts
+    let owned = OwnedLine {
+        level: 2,
+        host: "db-1".to_string(),
+        payload: vec![1, 2, 3],
+        message: "disk full".to_string(),
+    };
+    assert_eq!(data, owned.serialize_buffered());
+    assert_eq!(OwnedLine::deserialize(&data).unwrap(), (owned, data.len()));
+
+    let (decoded, bytes_read) = Line::deserialize_borrowed(&data).unwrap();
+    assert_eq!(bytes_read, data.len());
+    assert_eq!(decoded.host, "db-1");
+    assert_eq!(decoded.payload, [1, 2, 3]);
+    assert_eq!(decoded.note, "");
+    assert!(std::ptr::eq(decoded.host.as_ptr(), data[5..].as_ptr()));
+    assert!(matches!(decoded.message, Cow::Borrowed("disk full")));
+
+    for entry in [
+        Entry::Line(decoded),
+        Entry::Raw(Cow::Borrowed(&[4, 5])),
+        Entry::Owned(300, "owned".to_string()),
+    ] {
+        let mut data = vec![];
+        entry.encode(&mut data).unwrap();
+        assert_eq!(
+            Entry::deserialize_borrowed(&data).unwrap(),
+            (entry, data.len())
+        );
+    }
+
+    assert!(matches!(
+        Line::deserialize_borrowed(&data[..8]),
+        Err(SiriusError::NotEnoughData { offset: 1, ref path, .. })
+            if path.to_string() == "Line.host"
+    ));
+}
+
+#[test]
+fn test_borrow_containers_sirius() {
+    use sirius::{SiriusDe, SiriusEncode, SiriusError};
+
+    #[derive(Sirius, Debug, PartialEq)]
+    struct Origin {
+        port: u16,
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    #[sirius(borrow)]
+    struct Request<'a, T> {
+        tags: Vec<&'a str>,
+        referrer: Option<&'a str>,
+        origin: Option<Origin>,
+        extra: T,
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    struct OwnedRequest {
+        tags: Vec<String>,
+        referrer: Option<String>,
+        origin: Option<Origin>,
+        extra: Box<[String; 1]>,
+    }
+
+    let request = Request {
+        tags: vec!["a", "bc"],
+        referrer: Some("search"),
+        origin: None,
+        extra: Box::new(["x"]),
+    };
+
+    let mut data = vec![];
+    request.encode(&mut data).unwrap();
+    assert_eq!(request.encode_len(), data.len());
+
+    let owned = OwnedRequest {
+        tags: vec!["a".to_string(), "bc".to_string()],
+        referrer: Some("search".to_string()),
+        origin: None,
+        extra: Box::new(["x".to_string()]),
+    };
+    assert_eq!(data, owned.serialize_buffered());
+
+    let (decoded, bytes_read) = Request::<Box<[&str; 1]>>::deserialize_borrowed(&data).unwrap();
+    assert_eq!((&decoded, bytes_read), (&request, data.len()));
+    assert!(std::ptr::eq(decoded.tags[0].as_ptr(), data[8..].as_ptr()));
+
+    assert!(matches!(
+        Request::<u8>::deserialize_borrowed(&data[..10]),
+        Err(SiriusError::NotEnoughData { offset: 9, ref path, .. })
+            if path.to_string() == "Request.tags[1]"
+    ));
+}
+
+#[test]
+fn test_encoded_len_sirius() {
+    use sirius::{Config, IntEncoding};
+
+    mod upper {
+        pub fn serialize(
+            value: &str,
+            output: &mut impl sirius::Writer,
+        ) -> Result<usize, sirius::SiriusError> {
+            sirius::Sirius::serialize(&value.to_uppercase(), output)
+        }
+
+        pub fn deserialize(data: &[u8]) -> Result<(String, usize), sirius::SiriusError> {
+            sirius::Sirius::deserialize(data)
+        }
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    #[sirius(version = 2)]
+    struct Record {
+        id: u64,
+        #[sirius(varint)]
+        count: u32,
+        #[sirius(with = "upper")]
+        name: String,
+        #[sirius(since = 2, len = "u8")]
+        tags: Vec<String>,
+        #[sirius(skip)]
+        cache: u64,
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    #[sirius(tag_type = "varint")]
+    enum Shape {
+        Empty,
+        #[sirius(tag = 1000)]
+        Record(Record),
+        Point(i16, i16),
+    }
+
+    #[derive(Sirius, Debug, PartialEq)]
+    #[sirius(borrow)]
+    struct Line<'a>(&'a str, u8);
+
+    let record = Record {
+        id: 1,
+        count: 300,
+        name: "sirius".to_string(),
+        tags: vec!["a".to_string(), "bc".to_string()],
+        cache: 9,
+    };
+
+    for config in [
+        Config::new(),
+        Config::new().int_encoding(IntEncoding::Varint),
+    ] {
+        let mut data = vec![];
+        record.serialize_with(&mut data, &config).unwrap();
+        assert_eq!(record.encoded_len_with(&config), data.len());
+    }
+
+    for shape in [Shape::Empty, Shape::Point(-1, 2), Shape::Record(record)] {
+        let data = shape.serialize_buffered();
+        assert_eq!(shape.encoded_len(), data.len());
+
+        let mut buf = vec![0; shape.encoded_len()];
+        assert_eq!(shape.serialize_into_slice(&mut buf).unwrap(), data.len());
+        assert_eq!(buf, data);
+    }
+
+    let line = Line("borrowed", 1);
+    let mut data = vec![];
+    sirius::SiriusEncode::encode(&line, &mut data).unwrap();
+    assert_eq!(sirius::SiriusEncode::encode_len(&line), data.len());
+}
+
+#[test]
+fn test_utf8_sirius() {
+    use sirius::{Config, SiriusError, Utf8};
+
+    #[derive(Sirius, Debug, PartialEq)]
+    struct User {
+        id: u8,
+        name: String,
+        initial: char,
+    }
+
+    let data = [7, 0, 0, 0, 3, b'b', 0xc0, b'b', b'x'];
+    assert!(matches!(
+        User::deserialize(&data),
+        Err(SiriusError::InvalidUtf8 { offset: 6, ref path }) if path.to_string() == "User.name"
+    ));
+
+    let config = Config::new().utf8(Utf8::Lossy);
+    let (user, _) = User::deserialize_with(&data, &config).unwrap();
+    assert_eq!(user.name, "b\u{FFFD}b");
+
+    let data = [7, 0, 0, 0, 1, b'b', 0xff];
+    assert!(matches!(
+        User::deserialize_with(&data, &config),
+        Err(SiriusError::InvalidUtf8 { offset: 6, ref path }) if path.to_string() == "User.initial"
+    ));
+}
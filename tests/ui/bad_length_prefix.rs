@@ -0,0 +1,9 @@
+use sirius::Sirius;
+
+#[derive(Sirius)]
+struct Message {
+    #[sirius(len = "u24")]
+    body: Vec<u8>,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use sirius::Sirius;
+
+#[derive(Sirius)]
+#[sirius(tag_type = "u128")]
+enum Message {
+    Ping,
+}
+
+fn main() {}
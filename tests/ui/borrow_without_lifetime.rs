@@ -0,0 +1,9 @@
+use sirius::Sirius;
+
+#[derive(Sirius)]
+#[sirius(borrow)]
+struct Message {
+    body: String,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use sirius::Sirius;
+
+#[derive(Sirius)]
+struct Counter {
+    #[sirius(default = 1)]
+    count: u32,
+}
+
+fn main() {}
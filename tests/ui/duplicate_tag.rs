@@ -0,0 +1,10 @@
+use sirius::Sirius;
+
+#[derive(Sirius)]
+enum Message {
+    Ping = 1,
+    #[sirius(tag = 1)]
+    Pong,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use sirius::Sirius;
+
+#[derive(Sirius)]
+#[sirius(hash_tags)]
+struct Point {
+    x: u32,
+}
+
+fn main() {}
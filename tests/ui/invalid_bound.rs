@@ -0,0 +1,9 @@
+use sirius::Sirius;
+
+#[derive(Sirius)]
+#[sirius(bound = "T Sirius")]
+struct Wrapper<T> {
+    inner: T,
+}
+
+fn main() {}
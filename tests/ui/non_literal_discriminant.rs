@@ -0,0 +1,8 @@
+use sirius::Sirius;
+
+#[derive(Sirius)]
+enum Flags {
+    Read = 1 << 2,
+}
+
+fn main() {}
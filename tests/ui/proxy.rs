@@ -0,0 +1,11 @@
+use sirius::Sirius;
+
+#[derive(Sirius)]
+#[sirius(from = "u32", try_from = "u32")]
+struct Both(u32);
+
+#[derive(Sirius)]
+#[sirius(transparent, into = "u32")]
+struct Transparent(u32);
+
+fn main() {}
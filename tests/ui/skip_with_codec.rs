@@ -0,0 +1,11 @@
+use sirius::Sirius;
+
+mod codec {}
+
+#[derive(Sirius)]
+struct Counter {
+    #[sirius(skip, with = "codec")]
+    count: u32,
+}
+
+fn main() {}
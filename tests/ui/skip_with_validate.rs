@@ -0,0 +1,13 @@
+use sirius::Sirius;
+
+fn positive(value: &i32) -> Result<(), &'static str> {
+    if *value > 0 { Ok(()) } else { Err("not positive") }
+}
+
+#[derive(Sirius)]
+struct Counter {
+    #[sirius(skip, validate = "positive")]
+    count: i32,
+}
+
+fn main() {}
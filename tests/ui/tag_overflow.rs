@@ -0,0 +1,10 @@
+use sirius::Sirius;
+
+#[derive(Sirius)]
+#[sirius(tag_type = "u8")]
+enum Opcode {
+    Halt = 255,
+    Overflow,
+}
+
+fn main() {}
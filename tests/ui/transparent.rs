@@ -0,0 +1,29 @@
+use sirius::Sirius;
+
+#[derive(Sirius)]
+#[sirius(transparent)]
+struct Two {
+    a: u32,
+    b: u32,
+}
+
+#[derive(Sirius)]
+#[sirius(transparent)]
+struct AllSkipped {
+    #[sirius(skip)]
+    a: u32,
+}
+
+#[derive(Sirius)]
+#[sirius(transparent, version = 1)]
+struct Versioned {
+    a: u32,
+}
+
+#[derive(Sirius)]
+#[sirius(transparent)]
+enum Wrapper {
+    A(u32),
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use sirius::Sirius;
+
+#[derive(Sirius)]
+union Bits {
+    int: u32,
+    float: f32,
+}
+
+fn main() {}
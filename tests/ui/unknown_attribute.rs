@@ -0,0 +1,21 @@
+use sirius::Sirius;
+
+#[derive(Sirius)]
+#[sirius(rename = "point")]
+struct Point {
+    x: u32,
+}
+
+#[derive(Sirius)]
+struct Line {
+    #[sirius(flatten)]
+    from: u32,
+}
+
+#[derive(Sirius)]
+enum Shape {
+    #[sirius(index = 2)]
+    Circle,
+}
+
+fn main() {}
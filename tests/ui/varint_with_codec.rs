@@ -0,0 +1,11 @@
+use sirius::Sirius;
+
+mod codec {}
+
+#[derive(Sirius)]
+struct Counter {
+    #[sirius(varint, with = "codec")]
+    count: u32,
+}
+
+fn main() {}
@@ -0,0 +1,29 @@
+use sirius::Sirius;
+
+#[derive(Sirius)]
+struct Unversioned {
+    #[sirius(since = 1)]
+    added: u32,
+}
+
+#[derive(Sirius)]
+#[sirius(version = 2)]
+struct FromTheFuture {
+    #[sirius(since = 3)]
+    added: u32,
+}
+
+#[derive(Sirius)]
+#[sirius(version = 2)]
+struct Empty {
+    #[sirius(since = 2, until = 1)]
+    removed: u32,
+}
+
+#[derive(Sirius)]
+#[sirius(version = 2)]
+enum Versioned {
+    A,
+}
+
+fn main() {}